@@ -1,13 +1,17 @@
 use crate::cache::CacheExt;
+use crate::crypto::{decode_b64_url, owner_to_address};
+use crate::errors::ContractError;
 use crate::gql_result::GQLNodeParent;
 use crate::gql_result::GQLResultInterface;
 use crate::gql_result::GQLTransactionsResultInterface;
-use crate::gql_result::{GQLBundled, GQLEdgeInterface};
+use crate::gql_result::{GQLBundled, GQLEdgeInterface, GQLNodeInterface};
+use crate::merkle::validate_path;
 use crate::miscellaneous::ContractType;
 use crate::miscellaneous::{get_contract_type, get_contract_type_raw};
-use crate::utils::{decode_base_64, get_tags};
+use crate::utils::{decode_base_64, get_tags, hasher};
 use deno_core::error::AnyError;
 use deno_core::futures::stream;
+use deno_core::futures::Stream;
 use deno_core::futures::StreamExt;
 use once_cell::sync::OnceCell;
 use reqwest::Client;
@@ -66,6 +70,20 @@ pub struct TransactionData {
   pub data_root: String,
 }
 
+#[derive(Deserialize, Serialize, Default, Clone)]
+pub struct TransactionOffset {
+  pub size: String,
+  pub offset: String,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+pub struct TransactionChunk {
+  pub chunk: String,
+  pub data_path: String,
+  #[serde(default)]
+  pub tx_path: String,
+}
+
 #[derive(Deserialize, Serialize, Default, Clone)]
 pub struct BlockInfo {
   pub timestamp: u64,
@@ -103,6 +121,20 @@ pub struct Arweave {
   pub host: String,
   pub port: i32,
   pub protocol: ArweaveProtocol,
+  /// Whether contract, source and interaction transactions must carry a
+  /// valid owner signature before being evaluated.
+  pub verify_signatures: bool,
+  /// Whether contract sources and init states must be downloaded as chunks
+  /// and proven against the transaction's `data_root`.
+  pub verify_data: bool,
+  /// Whether interactions must satisfy the contract's `Min-Fee`,
+  /// `Interaction-Target` and `Min-Quantity` tags before being evaluated.
+  pub enforce_requirements: bool,
+  /// Whether the state change of every evaluated interaction should be
+  /// recorded.
+  pub trace: bool,
+  /// Maximum heap size of JavaScript contracts, in megabytes.
+  pub heap_limit: Option<usize>,
   client: Client,
 }
 
@@ -153,7 +185,12 @@ pub struct ManualLoadedContract {
 
 enum State {
   Next(Option<String>, InteractionVariables),
-  #[allow(dead_code)]
+  End,
+}
+
+enum ChunkState {
+  Start,
+  Next(usize, usize),
   End,
 }
 
@@ -180,6 +217,11 @@ impl Arweave {
         "http" => ArweaveProtocol::HTTP,
         "https" | _ => ArweaveProtocol::HTTPS,
       },
+      verify_signatures: false,
+      verify_data: false,
+      enforce_requirements: false,
+      trace: false,
+      heap_limit: None,
       client: Client::new(),
     }
   }
@@ -192,10 +234,40 @@ impl Arweave {
         "http" => ArweaveProtocol::HTTP,
         "https" | _ => ArweaveProtocol::HTTPS,
       },
+      verify_signatures: false,
+      verify_data: false,
+      enforce_requirements: false,
+      trace: false,
+      heap_limit: None,
       client: Client::new(),
     }
   }
 
+  pub fn with_signature_verification(mut self, verify: bool) -> Arweave {
+    self.verify_signatures = verify;
+    self
+  }
+
+  pub fn with_data_verification(mut self, verify: bool) -> Arweave {
+    self.verify_data = verify;
+    self
+  }
+
+  pub fn with_requirement_enforcement(mut self, enforce: bool) -> Arweave {
+    self.enforce_requirements = enforce;
+    self
+  }
+
+  pub fn with_tracing(mut self, trace: bool) -> Arweave {
+    self.trace = trace;
+    self
+  }
+
+  pub fn with_heap_limit(mut self, heap_limit: Option<usize>) -> Arweave {
+    self.heap_limit = heap_limit;
+    self
+  }
+
   pub async fn get_transaction(
     &self,
     transaction_id: &str,
@@ -204,12 +276,59 @@ impl Arweave {
       .client
       .get(format!("{}/tx/{}", self.get_host(), transaction_id))
       .send()
-      .await
-      .unwrap();
+      .await?;
     let transaction = request.json::<TransactionData>().await;
     transaction
   }
 
+  /// Fetches the full transaction behind an interaction and checks that it
+  /// is signed by the interaction owner and carries the same tags the
+  /// gateway returned through GraphQL.
+  pub async fn verify_interaction(
+    &self,
+    interaction: &GQLNodeInterface,
+  ) -> Result<(), AnyError> {
+    let transaction =
+      self.get_transaction(&interaction.id).await.map_err(|_| {
+        AnyError::msg(format!(
+          "Transaction {} could not be fetched for verification",
+          interaction.id
+        ))
+      })?;
+
+    transaction.verify_signature()?;
+
+    if owner_to_address(&transaction.owner)? != interaction.owner.address {
+      return Err(AnyError::msg(format!(
+        "Transaction {} is not signed by {}",
+        interaction.id, interaction.owner.address
+      )));
+    }
+
+    let mut signed_tags = Vec::with_capacity(transaction.tags.len());
+    for tag in &transaction.tags {
+      signed_tags.push((
+        String::from_utf8(decode_b64_url(&tag.name)?)?,
+        String::from_utf8(decode_b64_url(&tag.value)?)?,
+      ));
+    }
+
+    let tags_match = signed_tags.len() == interaction.tags.len()
+      && signed_tags
+        .iter()
+        .zip(interaction.tags.iter())
+        .all(|((name, value), tag)| *name == tag.name && *value == tag.value);
+
+    if !tags_match {
+      return Err(AnyError::msg(format!(
+        "Tags of transaction {} do not match its signed tags",
+        interaction.id
+      )));
+    }
+
+    Ok(())
+  }
+
   pub async fn get_bundled_contract(
     &self,
     transaction_id: &str,
@@ -218,20 +337,175 @@ impl Arweave {
       .client
       .get(format!("{}/{}", self.get_host(), transaction_id))
       .send()
-      .await
-      .unwrap();
+      .await?;
     let transaction = request.json::<BundledContract>().await;
     transaction
   }
 
-  pub async fn get_transaction_data(&self, transaction_id: &str) -> Vec<u8> {
+  pub async fn get_transaction_data(
+    &self,
+    transaction_id: &str,
+  ) -> reqwest::Result<Vec<u8>> {
     let request = self
       .client
       .get(format!("{}/{}", self.get_host(), transaction_id))
       .send()
-      .await
-      .unwrap();
-    request.bytes().await.unwrap().to_vec()
+      .await?;
+    Ok(request.bytes().await?.to_vec())
+  }
+
+  pub async fn get_transaction_offset(
+    &self,
+    transaction_id: &str,
+  ) -> reqwest::Result<TransactionOffset> {
+    let request = self
+      .client
+      .get(format!("{}/tx/{}/offset", self.get_host(), transaction_id))
+      .send()
+      .await?;
+    request.json::<TransactionOffset>().await
+  }
+
+  pub async fn get_chunk(
+    &self,
+    absolute_offset: usize,
+  ) -> reqwest::Result<TransactionChunk> {
+    let request = self
+      .client
+      .get(format!("{}/chunk/{}", self.get_host(), absolute_offset))
+      .send()
+      .await?;
+    request.json::<TransactionChunk>().await
+  }
+
+  /// Downloads the chunk starting `downloaded` bytes into the transaction
+  /// data and checks its merkle proof against `data_root`.
+  async fn get_verified_chunk(
+    &self,
+    transaction_id: &str,
+    data_root: &[u8],
+    data_size: usize,
+    absolute_offset: usize,
+    downloaded: usize,
+  ) -> Result<Vec<u8>, AnyError> {
+    let response = self.get_chunk(absolute_offset).await?;
+    let chunk = decode_b64_url(&response.chunk)?;
+    let data_path = decode_b64_url(&response.data_path)?;
+
+    validate_path(data_root, downloaded, data_size, &data_path)
+      .filter(|path| {
+        path.left_bound == downloaded
+          && path.chunk_size() == chunk.len()
+          && path.data_hash == hasher(&chunk)
+      })
+      .ok_or_else(|| {
+        AnyError::msg(format!(
+          "Chunk at offset {} of transaction {} does not match its data_root",
+          downloaded, transaction_id
+        ))
+      })?;
+
+    Ok(chunk)
+  }
+
+  /// Streams the data of a format 2 transaction chunk by chunk. Every
+  /// chunk is proven against `data_root` before it is yielded, and the
+  /// stream ends after the first error. Only the chunk being verified is
+  /// held in memory.
+  pub fn stream_transaction_chunks(
+    &self,
+    transaction: &TransactionData,
+  ) -> Result<impl Stream<Item = Result<Vec<u8>, AnyError>> + '_, AnyError> {
+    let transaction_id = transaction.id.to_owned();
+    let data_root = decode_b64_url(&transaction.data_root)?;
+    let data_size = transaction.data_size.parse::<usize>()?;
+
+    Ok(stream::unfold(ChunkState::Start, move |state| {
+      let transaction_id = transaction_id.to_owned();
+      let data_root = data_root.to_owned();
+
+      async move {
+        let (start_offset, downloaded) = match state {
+          ChunkState::End => return None,
+          ChunkState::Next(start_offset, downloaded) => {
+            (start_offset, downloaded)
+          }
+          ChunkState::Start => {
+            match self.get_start_offset(&transaction_id, data_size).await {
+              Ok(start_offset) => (start_offset, 0),
+              Err(err) => return Some((Err(err), ChunkState::End)),
+            }
+          }
+        };
+
+        if downloaded >= data_size {
+          return None;
+        }
+
+        match self
+          .get_verified_chunk(
+            &transaction_id,
+            &data_root,
+            data_size,
+            start_offset + downloaded,
+            downloaded,
+          )
+          .await
+        {
+          Ok(chunk) => {
+            let downloaded = downloaded + chunk.len();
+            Some((Ok(chunk), ChunkState::Next(start_offset, downloaded)))
+          }
+          Err(err) => Some((Err(err), ChunkState::End)),
+        }
+      }
+    }))
+  }
+
+  /// Absolute weave offset of the first byte of a transaction's data.
+  async fn get_start_offset(
+    &self,
+    transaction_id: &str,
+    data_size: usize,
+  ) -> Result<usize, AnyError> {
+    let offset = self.get_transaction_offset(transaction_id).await?;
+    let size = offset.size.parse::<usize>()?;
+    let end_offset = offset.offset.parse::<usize>()?;
+
+    if size != data_size || end_offset + 1 < size {
+      return Err(AnyError::msg(format!(
+        "Data offset of transaction {} does not match its data_size",
+        transaction_id
+      )));
+    }
+
+    Ok(end_offset + 1 - size)
+  }
+
+  /// Like `get_transaction_data`, but proves the data against the
+  /// transaction's `data_root` instead of trusting the gateway. The chunks
+  /// of the data are collected as they are verified, so memory use grows
+  /// with the size of the data and is not bounded: contract runtimes need
+  /// the whole source. Consumers that do not should read
+  /// `stream_transaction_chunks` instead.
+  pub async fn get_verified_transaction_data(
+    &self,
+    transaction: &TransactionData,
+  ) -> Result<Vec<u8>, AnyError> {
+    // Format 1 data is part of the signed transaction itself.
+    if transaction.format == 1 {
+      return decode_b64_url(&transaction.data);
+    }
+
+    // Not preallocated from `data_size`, which the gateway may inflate.
+    let mut data = Vec::new();
+    let mut chunks = Box::pin(self.stream_transaction_chunks(transaction)?);
+
+    while let Some(chunk) = chunks.next().await {
+      data.extend(chunk?);
+    }
+
+    Ok(data)
   }
 
   pub async fn get_transaction_block(
@@ -256,17 +530,14 @@ impl Arweave {
     request.json::<BlockInfo>().await
   }
 
-  pub async fn get_network_info(&self) -> NetworkInfo {
-    let info = self
+  pub async fn get_network_info(&self) -> reqwest::Result<NetworkInfo> {
+    self
       .client
       .get(format!("{}/info", self.get_host()))
       .send()
-      .await
-      .unwrap()
+      .await?
       .json::<NetworkInfo>()
       .await
-      .unwrap();
-    info
   }
 
   pub async fn get_interactions(
@@ -274,12 +545,12 @@ impl Arweave {
     contract_id: String,
     height: Option<usize>,
     cache: bool,
-  ) -> Result<(Vec<GQLEdgeInterface>, usize, bool), AnyError> {
+  ) -> Result<(Vec<GQLEdgeInterface>, usize, bool), ContractError> {
     let mut interactions: Option<Vec<GQLEdgeInterface>> = None;
 
     let height_result = match height {
       Some(size) => size,
-      None => self.get_network_info().await.height,
+      None => self.get_network_info().await?.height,
     };
 
     if cache {
@@ -319,7 +590,7 @@ impl Arweave {
             Some(last_transaction_edge.cursor.to_owned()),
             variables.to_owned(),
           )
-          .await;
+          .await?;
 
         for result in fetch_more_interactions {
           let mut new_tx_infos = result.edges.clone();
@@ -345,7 +616,7 @@ impl Arweave {
         cursor = Some(owned.cursor.to_owned());
       }
 
-      let results = self.stream_interactions(cursor, variables).await;
+      let results = self.stream_interactions(cursor, variables).await?;
 
       for result in results {
         let mut new_tx_infos = result.edges.clone();
@@ -406,7 +677,7 @@ impl Arweave {
     mut variables: InteractionVariables,
     from_last_page: bool,
     max_results: Option<usize>,
-  ) -> Result<GQLTransactionsResultInterface, AnyError> {
+  ) -> Result<GQLTransactionsResultInterface, ContractError> {
     let mut query = String::from(
       r#"query Transactions($tags: [TagFilter!]!, $blockFilter: BlockFilter!, $first: Int!, $after: String) {
     transactions(tags: $tags, block: $blockFilter, first: $first, sort: HEIGHT_ASC, after: $after) {
@@ -450,14 +721,63 @@ impl Arweave {
       .post(req_url)
       .json(&graphql_query)
       .send()
-      .await
-      .unwrap();
+      .await?;
 
     let data = result.json::<GQLResultInterface>().await?;
 
     Ok(data.data.transactions)
   }
 
+  /// Checks the signature of `transaction` when signature verification is
+  /// enabled.
+  fn check_signature(
+    &self,
+    transaction: &TransactionData,
+  ) -> Result<(), ContractError> {
+    if self.verify_signatures {
+      transaction
+        .verify_signature()
+        .map_err(|err| ContractError::Verification(err.to_string()))?;
+    }
+
+    Ok(())
+  }
+
+  /// Downloads the data of `transaction`, proving it against its
+  /// `data_root` when data verification is enabled.
+  async fn load_transaction_data(
+    &self,
+    transaction: &TransactionData,
+  ) -> Result<Vec<u8>, ContractError> {
+    if self.verify_data {
+      self
+        .get_verified_transaction_data(transaction)
+        .await
+        .map_err(|err| ContractError::Verification(err.to_string()))
+    } else {
+      Ok(self.get_transaction_data(&transaction.id).await?)
+    }
+  }
+
+  /// Loads the source a contract evolves to, along with the runtime it
+  /// needs.
+  pub async fn load_contract_source(
+    &self,
+    source_tx_id: &str,
+  ) -> Result<ManualLoadedContract, ContractError> {
+    let source_tx = self.get_transaction(source_tx_id).await?;
+
+    self.check_signature(&source_tx)?;
+
+    let contract_src = self.load_transaction_data(&source_tx).await?;
+    let contract_type = get_contract_type(None, &source_tx, &source_tx)?;
+
+    Ok(ManualLoadedContract {
+      contract_src,
+      contract_type,
+    })
+  }
+
   pub async fn load_contract(
     &self,
     contract_id: String,
@@ -467,54 +787,59 @@ impl Arweave {
     cache: bool,
     simulated: bool,
     is_contract_in_bundled: bool,
-  ) -> Result<LoadedContract, AnyError> {
+  ) -> Result<LoadedContract, ContractError> {
     let mut result: Option<LoadedContract> = None;
 
     if is_contract_in_bundled {
-      if let Ok(bundle_tx_search) =
-        self.get_bundled_contract(&contract_id.clone()).await
-      {
-        let owner = bundle_tx_search
-          .contractOwner
-          .unwrap_or_else(|| String::new());
-        let content_type = bundle_tx_search
-          .contentType
-          .unwrap_or_else(|| String::new());
-        let mut init_state =
-          bundle_tx_search.initState.unwrap_or_else(|| String::new());
-        let contract_data = bundle_tx_search.contractSrc;
-
-        if simulated {
-          if let Some(user_init_state) = contract_init_state {
-            init_state = user_init_state;
-          }
-        }
+      if self.verify_signatures {
+        return Err(ContractError::Verification(String::from(
+          "Signatures of bundled contracts cannot be verified",
+        )));
+      }
 
-        return Ok(LoadedContract {
-          id: contract_id.clone(),
-          contract_src_tx_id: contract_id.clone(),
-          contract_src: contract_data,
-          contract_type: get_contract_type_raw(content_type),
-          init_state,
-          min_fee: None,
-          contract_transaction: TransactionData {
-            format: 2,
-            id: contract_id,
-            last_tx: String::new(),
-            owner,
-            tags: vec![],
-            target: String::new(),
-            quantity: String::new(),
-            data: String::new(),
-            reward: String::new(),
-            signature: String::new(),
-            data_size: String::new(),
-            data_root: String::new(),
-          },
-        });
-      } else {
-        panic!("Bundled contract was not found during query")
+      let bundle_tx_search = self
+        .get_bundled_contract(&contract_id.clone())
+        .await
+        .map_err(|_| ContractError::NotFound(contract_id.to_owned()))?;
+
+      let owner = bundle_tx_search
+        .contractOwner
+        .unwrap_or_else(|| String::new());
+      let content_type = bundle_tx_search
+        .contentType
+        .unwrap_or_else(|| String::new());
+      let mut init_state =
+        bundle_tx_search.initState.unwrap_or_else(|| String::new());
+      let contract_data = bundle_tx_search.contractSrc;
+
+      if simulated {
+        if let Some(user_init_state) = contract_init_state {
+          init_state = user_init_state;
+        }
       }
+
+      return Ok(LoadedContract {
+        id: contract_id.clone(),
+        contract_src_tx_id: contract_id.clone(),
+        contract_src: contract_data,
+        contract_type: get_contract_type_raw(content_type),
+        init_state,
+        min_fee: None,
+        contract_transaction: TransactionData {
+          format: 2,
+          id: contract_id,
+          last_tx: String::new(),
+          owner,
+          tags: vec![],
+          target: String::new(),
+          quantity: String::new(),
+          data: String::new(),
+          reward: String::new(),
+          signature: String::new(),
+          data_size: String::new(),
+          data_root: String::new(),
+        },
+      });
     }
 
     if cache {
@@ -527,6 +852,8 @@ impl Arweave {
     if result.is_some() {
       let mut cached_result = result.unwrap();
 
+      self.check_signature(&cached_result.contract_transaction)?;
+
       if simulated {
         if let Some(init_state) = contract_init_state {
           cached_result.init_state = init_state;
@@ -537,18 +864,29 @@ impl Arweave {
     } else {
       let contract_transaction = self.get_transaction(&contract_id).await?;
 
+      self.check_signature(&contract_transaction)?;
+
       let contract_src = contract_src_tx_id
         .or_else(|| contract_transaction.get_tag("Contract-Src").ok())
-        .ok_or_else(|| {
-          AnyError::msg("Contract-Src tag not found in transaction")
+        .ok_or_else(|| ContractError::MissingTag {
+          tag: String::from("Contract-Src"),
+          transaction: contract_id.to_owned(),
         })?;
 
       let min_fee = contract_transaction.get_tag("Min-Fee").ok();
 
       let contract_src_tx = self.get_transaction(&contract_src).await?;
 
+      self.check_signature(&contract_src_tx)?;
+
       let contract_src_data =
-        self.get_transaction_data(&contract_src_tx.id).await;
+        self.load_transaction_data(&contract_src_tx).await?;
+
+      let invalid_init_state =
+        |err: std::string::FromUtf8Error| ContractError::InitState {
+          contract: contract_id.to_owned(),
+          reason: err.to_string(),
+        };
 
       let mut state: String;
 
@@ -562,15 +900,27 @@ impl Arweave {
         {
           let init_state_tx =
             self.get_transaction(&init_state_tag_txid).await?;
-          state = decode_base_64(init_state_tx.data);
+          self.check_signature(&init_state_tx)?;
+          if self.verify_data {
+            state = String::from_utf8(
+              self.load_transaction_data(&init_state_tx).await?,
+            )
+            .map_err(invalid_init_state)?;
+          } else {
+            state = decode_base_64(init_state_tx.data);
+          }
         } else {
-          state = decode_base_64(contract_transaction.data.to_owned());
+          state = if self.verify_data {
+            String::new()
+          } else {
+            decode_base_64(contract_transaction.data.to_owned())
+          };
 
           if state.is_empty() {
             state = String::from_utf8(
-              self.get_transaction_data(&contract_transaction.id).await,
+              self.load_transaction_data(&contract_transaction).await?,
             )
-            .unwrap();
+            .map_err(invalid_init_state)?;
           }
         }
       }
@@ -641,7 +991,7 @@ impl Arweave {
     &self,
     cursor: Option<String>,
     variables: InteractionVariables,
-  ) -> Vec<GQLTransactionsResultInterface> {
+  ) -> Result<Vec<GQLTransactionsResultInterface>, ContractError> {
     stream::unfold(State::Next(cursor, variables), |state| async move {
       match state {
         State::End => None,
@@ -650,10 +1000,13 @@ impl Arweave {
 
           new_variables.after = cursor;
 
-          let tx = self
+          let tx = match self
             .get_next_interaction_page(new_variables, false, None)
             .await
-            .unwrap();
+          {
+            Ok(tx) => tx,
+            Err(err) => return Some((Err(err), State::End)),
+          };
 
           if tx.edges.is_empty() {
             None
@@ -664,7 +1017,7 @@ impl Arweave {
 
             if let Some(result_edge) = edge {
               let cursor = result_edge.cursor.to_owned();
-              Some((tx, State::Next(Some(cursor), variables)))
+              Some((Ok(tx), State::Next(Some(cursor), variables)))
             } else {
               None
             }
@@ -672,8 +1025,10 @@ impl Arweave {
         }
       }
     })
-    .collect::<Vec<GQLTransactionsResultInterface>>()
+    .collect::<Vec<Result<GQLTransactionsResultInterface, ContractError>>>()
     .await
+    .into_iter()
+    .collect()
   }
 
   fn get_max_edges(&self, data: &[GQLEdgeInterface]) -> usize {
@@ -691,7 +1046,7 @@ impl Arweave {
     &self,
     variables: &InteractionVariables,
     cursor: String,
-  ) -> Result<bool, AnyError> {
+  ) -> Result<bool, ContractError> {
     let mut variables = variables.to_owned();
     variables.after = Some(cursor);
     variables.first = 1;
@@ -1,12 +1,15 @@
 use crate::arweave::LoadedContract;
 use crate::gql_result::GQLEdgeInterface;
+use crate::miscellaneous::{Evolution, ValidityRecords};
 use deno_core::serde_json::Value;
 use indexmap::map::IndexMap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::fs::{create_dir_all, remove_file, File};
+use std::fs::{create_dir_all, read_dir, remove_file, File};
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 pub trait CacheExt: Debug {
   fn new() -> Self
@@ -38,6 +41,86 @@ pub struct ArweaveCache {
 pub struct StateResult {
   pub state: Value,
   pub validity: IndexMap<String, Value>,
+  #[serde(default)]
+  pub evolutions: Vec<Evolution>,
+  #[serde(default)]
+  pub requests: RecordedRequests,
+  #[serde(default)]
+  pub records: ValidityRecords,
+}
+
+/// Deterministic fetch responses keyed by interaction id and request hash.
+pub type RecordedRequests = HashMap<String, HashMap<String, Value>>;
+
+/// What a file of the cache holds about its contract.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+  Contract,
+  Interactions,
+  State,
+}
+
+impl CacheKind {
+  pub const ALL: [CacheKind; 3] = [
+    CacheKind::Contract,
+    CacheKind::Interactions,
+    CacheKind::State,
+  ];
+
+  /// Folder of the cache holding files of this kind.
+  pub fn folder(&self) -> &'static str {
+    match self {
+      CacheKind::Contract => "contracts",
+      CacheKind::Interactions => "interactions",
+      CacheKind::State => "states",
+    }
+  }
+
+  pub fn from_folder(folder: &str) -> Option<CacheKind> {
+    CacheKind::ALL
+      .into_iter()
+      .find(|kind| kind.folder() == folder)
+  }
+
+  pub fn file_name(&self, contract_id: &str) -> String {
+    match self {
+      CacheKind::State => format!("{}_result.json", contract_id),
+      _ => format!("{}.json", contract_id),
+    }
+  }
+
+  /// Contract a file of this kind belongs to, if its name is one the cache
+  /// writes.
+  pub fn contract_id(&self, file_name: &str) -> Option<String> {
+    let suffix = match self {
+      CacheKind::State => "_result.json",
+      _ => ".json",
+    };
+    file_name
+      .strip_suffix(suffix)
+      .filter(|id| !id.is_empty())
+      .map(String::from)
+  }
+}
+
+/// File of the cache.
+#[derive(Clone, Debug)]
+pub struct CacheFile {
+  pub contract_id: String,
+  pub kind: CacheKind,
+  pub path: PathBuf,
+  pub size: u64,
+  pub modified: SystemTime,
+}
+
+/// Folder holding the cache: `THREE_EM_CACHE_DIR` when set, `3em` in the
+/// user's cache folder otherwise.
+pub fn cache_root() -> Option<PathBuf> {
+  match std::env::var_os("THREE_EM_CACHE_DIR") {
+    Some(dir) => Some(PathBuf::from(dir)),
+    None => dirs::cache_dir().map(|dir| dir.join("3em")),
+  }
 }
 
 impl Default for ArweaveCache {
@@ -48,20 +131,8 @@ impl Default for ArweaveCache {
 
 impl CacheExt for ArweaveCache {
   fn new() -> ArweaveCache {
-    if let Some(cache_dir) = dirs::cache_dir() {
-      let root_cache_dir = cache_dir.join("3em").join("contracts");
-      let interactions_cache_dir = cache_dir.join("3em").join("interactions");
-      let states_cache_dir = cache_dir.join("3em").join("states");
-
-      create_dir_all(&root_cache_dir).unwrap();
-      create_dir_all(&interactions_cache_dir).unwrap();
-      create_dir_all(&states_cache_dir).unwrap();
-
-      ArweaveCache {
-        contracts_cache_folder: root_cache_dir,
-        interactions_cache_folder: interactions_cache_dir,
-        states_cache_folder: states_cache_dir,
-      }
+    if let Some(cache_dir) = cache_root() {
+      ArweaveCache::at(&cache_dir)
     } else {
       panic!("Cache folder could not be set");
     }
@@ -151,6 +222,71 @@ impl CacheExt for ArweaveCache {
 }
 
 impl ArweaveCache {
+  /// Cache held by `root` instead of the default folder.
+  pub fn at(root: &Path) -> ArweaveCache {
+    let root_cache_dir = root.join(CacheKind::Contract.folder());
+    let interactions_cache_dir = root.join(CacheKind::Interactions.folder());
+    let states_cache_dir = root.join(CacheKind::State.folder());
+
+    create_dir_all(&root_cache_dir).unwrap();
+    create_dir_all(&interactions_cache_dir).unwrap();
+    create_dir_all(&states_cache_dir).unwrap();
+
+    ArweaveCache {
+      contracts_cache_folder: root_cache_dir,
+      interactions_cache_folder: interactions_cache_dir,
+      states_cache_folder: states_cache_dir,
+    }
+  }
+
+  pub fn folder(&self, kind: CacheKind) -> &Path {
+    match kind {
+      CacheKind::Contract => &self.contracts_cache_folder,
+      CacheKind::Interactions => &self.interactions_cache_folder,
+      CacheKind::State => &self.states_cache_folder,
+    }
+  }
+
+  /// Files of the cache, sorted by contract. Files the cache did not write
+  /// are left out.
+  pub fn files(&self) -> std::io::Result<Vec<CacheFile>> {
+    let mut files = Vec::new();
+    for kind in CacheKind::ALL {
+      for entry in read_dir(self.folder(kind))? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let contract_id = match entry
+          .file_name()
+          .to_str()
+          .and_then(|name| kind.contract_id(name))
+        {
+          Some(contract_id) if metadata.is_file() => contract_id,
+          _ => continue,
+        };
+
+        files.push(CacheFile {
+          contract_id,
+          kind,
+          path: entry.path(),
+          size: metadata.len(),
+          modified: metadata.modified()?,
+        });
+      }
+    }
+
+    files.sort_by(|a, b| {
+      a.contract_id.cmp(&b.contract_id).then(a.kind.cmp(&b.kind))
+    });
+    Ok(files)
+  }
+
+  pub fn remove_files(&self, files: &[CacheFile]) -> std::io::Result<()> {
+    for file in files {
+      remove_file(&file.path)?;
+    }
+    Ok(())
+  }
+
   pub async fn delete_cache_interactions(&self, contract_id: String) {
     let cache_file = self.get_cache_interaction_file(contract_id);
     remove_file(cache_file).unwrap();
@@ -159,7 +295,7 @@ impl ArweaveCache {
   fn get_cache_file(&self, contract_id: String) -> PathBuf {
     let mut cache_file = self.contracts_cache_folder.to_owned();
 
-    cache_file.push(format!("{}.json", contract_id));
+    cache_file.push(CacheKind::Contract.file_name(&contract_id));
 
     cache_file
   }
@@ -167,7 +303,7 @@ impl ArweaveCache {
   fn get_cache_interaction_file(&self, contract_id: String) -> PathBuf {
     let mut cache_file = self.interactions_cache_folder.to_owned();
 
-    cache_file.push(format!("{}.json", contract_id));
+    cache_file.push(CacheKind::Interactions.file_name(&contract_id));
 
     cache_file
   }
@@ -175,7 +311,7 @@ impl ArweaveCache {
   fn get_cache_state_file(&self, contract_id: String) -> PathBuf {
     let mut cache_file = self.states_cache_folder.to_owned();
 
-    cache_file.push(format!("{}_result.json", contract_id));
+    cache_file.push(CacheKind::State.file_name(&contract_id));
 
     cache_file
   }
@@ -0,0 +1,194 @@
+use crate::arweave::TransactionData;
+use deno_core::error::AnyError;
+use rsa::{BigUint, PaddingScheme, PublicKey, RsaPublicKey};
+use sha2::Digest;
+
+/// Public exponent used by every Arweave wallet (`AQAB`).
+const ARWEAVE_PUBLIC_EXPONENT: u32 = 65537;
+
+/// A node of the structure given to `deep_hash`.
+pub enum DeepHashChunk {
+  Blob(Vec<u8>),
+  List(Vec<DeepHashChunk>),
+}
+
+fn sha384(data: &[u8]) -> Vec<u8> {
+  let mut hasher = sha2::Sha384::new();
+  hasher.update(data);
+  hasher.finalize()[..].to_vec()
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+  let mut hasher = sha2::Sha256::new();
+  hasher.update(data);
+  hasher.finalize()[..].to_vec()
+}
+
+/// Arweave's deep hash algorithm, as implemented in `arweave-js`.
+pub fn deep_hash(chunk: &DeepHashChunk) -> Vec<u8> {
+  match chunk {
+    DeepHashChunk::Blob(data) => {
+      let tag = format!("blob{}", data.len());
+      let mut tagged_hash = sha384(tag.as_bytes());
+      tagged_hash.extend_from_slice(&sha384(data));
+      sha384(&tagged_hash)
+    }
+    DeepHashChunk::List(chunks) => {
+      let tag = format!("list{}", chunks.len());
+      chunks.iter().fold(sha384(tag.as_bytes()), |acc, chunk| {
+        let mut pair = acc;
+        pair.extend_from_slice(&deep_hash(chunk));
+        sha384(&pair)
+      })
+    }
+  }
+}
+
+pub fn decode_b64_url(data: &str) -> Result<Vec<u8>, AnyError> {
+  Ok(base64::decode_config(data, base64::URL_SAFE_NO_PAD)?)
+}
+
+pub fn encode_b64_url(data: &[u8]) -> String {
+  base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// Wallet address owning the given public key modulus.
+pub fn owner_to_address(owner: &str) -> Result<String, AnyError> {
+  Ok(encode_b64_url(&sha256(&decode_b64_url(owner)?)))
+}
+
+impl TransactionData {
+  /// Bytes the owner of the transaction signed, for both format 1 and
+  /// format 2 transactions.
+  pub fn get_signature_data(&self) -> Result<Vec<u8>, AnyError> {
+    let mut tags: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(self.tags.len());
+    for tag in &self.tags {
+      tags.push((decode_b64_url(&tag.name)?, decode_b64_url(&tag.value)?));
+    }
+
+    match self.format {
+      1 => {
+        let mut data = decode_b64_url(&self.owner)?;
+        data.extend(decode_b64_url(&self.target)?);
+        data.extend(decode_b64_url(&self.data)?);
+        data.extend(self.quantity.as_bytes());
+        data.extend(self.reward.as_bytes());
+        data.extend(decode_b64_url(&self.last_tx)?);
+        for (name, value) in tags {
+          data.extend(name);
+          data.extend(value);
+        }
+        Ok(data)
+      }
+      2 => {
+        let tag_list = tags
+          .into_iter()
+          .map(|(name, value)| {
+            DeepHashChunk::List(vec![
+              DeepHashChunk::Blob(name),
+              DeepHashChunk::Blob(value),
+            ])
+          })
+          .collect();
+
+        Ok(deep_hash(&DeepHashChunk::List(vec![
+          DeepHashChunk::Blob(self.format.to_string().into_bytes()),
+          DeepHashChunk::Blob(decode_b64_url(&self.owner)?),
+          DeepHashChunk::Blob(decode_b64_url(&self.target)?),
+          DeepHashChunk::Blob(self.quantity.as_bytes().to_vec()),
+          DeepHashChunk::Blob(self.reward.as_bytes().to_vec()),
+          DeepHashChunk::Blob(decode_b64_url(&self.last_tx)?),
+          DeepHashChunk::List(tag_list),
+          DeepHashChunk::Blob(self.data_size.as_bytes().to_vec()),
+          DeepHashChunk::Blob(decode_b64_url(&self.data_root)?),
+        ])))
+      }
+      format => Err(AnyError::msg(format!(
+        "Unsupported transaction format {}",
+        format
+      ))),
+    }
+  }
+
+  /// Checks that the transaction id derives from its signature and that the
+  /// signature was produced by `owner` over the transaction fields.
+  pub fn verify_signature(&self) -> Result<(), AnyError> {
+    let signature = decode_b64_url(&self.signature)?;
+    if signature.is_empty() {
+      return Err(AnyError::msg(format!(
+        "Transaction {} is not signed",
+        self.id
+      )));
+    }
+
+    if encode_b64_url(&sha256(&signature)) != self.id {
+      return Err(AnyError::msg(format!(
+        "Transaction id {} does not match its signature",
+        self.id
+      )));
+    }
+
+    let public_key = RsaPublicKey::new(
+      BigUint::from_bytes_be(&decode_b64_url(&self.owner)?),
+      BigUint::from(ARWEAVE_PUBLIC_EXPONENT),
+    )?;
+    let hashed = sha256(&self.get_signature_data()?);
+
+    public_key
+      .verify(
+        PaddingScheme::new_pss::<sha2::Sha256>(),
+        &hashed,
+        &signature,
+      )
+      .map_err(|_| {
+        AnyError::msg(format!("Invalid signature for transaction {}", self.id))
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::arweave::TransactionData;
+  use crate::crypto::{deep_hash, owner_to_address, DeepHashChunk};
+
+  fn signed_transaction() -> TransactionData {
+    deno_core::serde_json::from_str(include_str!(
+      "../../testdata/arweave/signed_tx.json"
+    ))
+    .unwrap()
+  }
+
+  #[test]
+  fn test_deep_hash() {
+    let hash = deep_hash(&DeepHashChunk::List(vec![
+      DeepHashChunk::Blob(b"3em".to_vec()),
+      DeepHashChunk::List(vec![]),
+    ]));
+    assert_eq!(
+      hex::encode(hash),
+      "2d261388c627c3a991f0f95e79f0200cd0b4d480aa71e922eb5898d1b3cd002bb5d52fc3b92f90338d9587afbfd5a2f3"
+    );
+  }
+
+  #[test]
+  fn test_verify_signature() {
+    let tx = signed_transaction();
+    assert!(tx.verify_signature().is_ok());
+    assert_eq!(
+      owner_to_address(&tx.owner).unwrap(),
+      "wCNx5iYyZ99zaO5NDAPgmJIk1B7KniODnset2eb99S4"
+    );
+  }
+
+  #[test]
+  fn test_verify_signature_tampered() {
+    let mut tx = signed_transaction();
+    tx.quantity = String::from("1000");
+    let err = tx.verify_signature().unwrap_err();
+    assert!(err.to_string().contains("Invalid signature"));
+
+    let mut tx = signed_transaction();
+    tx.signature = String::new();
+    assert!(tx.verify_signature().is_err());
+  }
+}
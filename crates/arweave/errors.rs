@@ -0,0 +1,91 @@
+use thiserror::Error;
+
+/// Errors raised while loading or evaluating a contract.
+#[derive(Debug, Error)]
+pub enum ContractError {
+  #[error("Request to gateway failed: {0}")]
+  Network(String),
+  #[error("Contract {0} was not found")]
+  NotFound(String),
+  #[error("{tag} tag not found in transaction {transaction}")]
+  MissingTag { tag: String, transaction: String },
+  #[error("Source of contract {contract} could not be decoded: {reason}")]
+  SourceEncoding { contract: String, reason: String },
+  #[error("Invalid init state for contract {contract}: {reason}")]
+  InitState { contract: String, reason: String },
+  #[error("Invalid EXM context: {0}")]
+  ExmContext(String),
+  #[error(
+    "Runtime for contract {contract} could not be initialized: {reason}"
+  )]
+  RuntimeInit { contract: String, reason: String },
+  #[error("Contract {contract} could not evolve to {source_tx}: {reason}")]
+  Evolve {
+    contract: String,
+    source_tx: String,
+    reason: String,
+  },
+  #[error(
+    "Interaction {interaction} of contract {contract} diverged from its recorded requests: {reason}"
+  )]
+  RequestDivergence {
+    contract: String,
+    interaction: String,
+    reason: String,
+  },
+  #[error("Verification failed: {0}")]
+  Verification(String),
+  #[error("Evaluation of contract {contract} failed: {reason}")]
+  Execution { contract: String, reason: String },
+  #[error("Height {from} is above height {to}")]
+  HeightRange { from: usize, to: usize },
+}
+
+impl ContractError {
+  /// Short, stable identifier of the error variant, used by the server and
+  /// the node bindings.
+  pub fn kind(&self) -> &'static str {
+    match self {
+      ContractError::Network(_) => "network",
+      ContractError::NotFound(_) => "not_found",
+      ContractError::MissingTag { .. } => "missing_tag",
+      ContractError::SourceEncoding { .. } => "source_encoding",
+      ContractError::InitState { .. } => "init_state",
+      ContractError::ExmContext(_) => "exm_context",
+      ContractError::RuntimeInit { .. } => "runtime_init",
+      ContractError::Evolve { .. } => "evolve",
+      ContractError::RequestDivergence { .. } => "request_divergence",
+      ContractError::Verification(_) => "verification",
+      ContractError::Execution { .. } => "execution",
+      ContractError::HeightRange { .. } => "height_range",
+    }
+  }
+}
+
+impl From<reqwest::Error> for ContractError {
+  fn from(err: reqwest::Error) -> Self {
+    ContractError::Network(err.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::errors::ContractError;
+
+  #[test]
+  fn test_error_display() {
+    let err = ContractError::MissingTag {
+      tag: String::from("Contract-Src"),
+      transaction: String::from("abc"),
+    };
+    assert_eq!(
+      err.to_string(),
+      "Contract-Src tag not found in transaction abc"
+    );
+    assert_eq!(err.kind(), "missing_tag");
+
+    let err: deno_core::error::AnyError =
+      ContractError::NotFound(String::from("abc")).into();
+    assert_eq!(err.to_string(), "Contract abc was not found");
+  }
+}
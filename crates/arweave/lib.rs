@@ -1,6 +1,9 @@
 pub mod arweave;
 pub mod cache;
+pub mod crypto;
+pub mod errors;
 pub mod gql_result;
 pub mod lru_cache;
+pub mod merkle;
 pub mod miscellaneous;
 mod utils;
@@ -0,0 +1,156 @@
+use crate::utils::hasher;
+
+pub const HASH_SIZE: usize = 32;
+pub const NOTE_SIZE: usize = 32;
+
+/// Byte range of the transaction data proven by a merkle path.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ValidatedPath {
+  pub left_bound: usize,
+  pub right_bound: usize,
+  /// SHA-256 of the chunk the path leads to.
+  pub data_hash: Vec<u8>,
+}
+
+impl ValidatedPath {
+  pub fn chunk_size(&self) -> usize {
+    self.right_bound - self.left_bound
+  }
+}
+
+fn note_to_usize(note: &[u8]) -> Option<usize> {
+  let (high, low) = note.split_at(NOTE_SIZE - 8);
+  if high.iter().any(|byte| *byte != 0) {
+    return None;
+  }
+  let mut bytes = [0u8; 8];
+  bytes.copy_from_slice(low);
+  usize::try_from(u64::from_be_bytes(bytes)).ok()
+}
+
+fn hash_concat(parts: &[&[u8]]) -> Vec<u8> {
+  let hashes: Vec<u8> = parts.iter().flat_map(|part| hasher(part)).collect();
+  hasher(&hashes)
+}
+
+/// Walks a `data_path` merkle proof from `data_root` down to the chunk
+/// holding byte `dest`, following `validatePath` in `arweave-js`.
+pub fn validate_path(
+  data_root: &[u8],
+  dest: usize,
+  data_size: usize,
+  path: &[u8],
+) -> Option<ValidatedPath> {
+  if data_size == 0 {
+    return None;
+  }
+
+  let dest = dest.min(data_size - 1);
+  let mut id = data_root.to_vec();
+  let mut left_bound = 0;
+  let mut right_bound = data_size;
+  let mut path = path;
+
+  loop {
+    if path.len() == HASH_SIZE + NOTE_SIZE {
+      let (data_hash, note) = path.split_at(HASH_SIZE);
+      if hash_concat(&[data_hash, note]) != id {
+        return None;
+      }
+
+      return Some(ValidatedPath {
+        left_bound,
+        right_bound,
+        data_hash: data_hash.to_vec(),
+      });
+    }
+
+    if path.len() < HASH_SIZE * 2 + NOTE_SIZE {
+      return None;
+    }
+
+    let (left, rest) = path.split_at(HASH_SIZE);
+    let (right, rest) = rest.split_at(HASH_SIZE);
+    let (note, remainder) = rest.split_at(NOTE_SIZE);
+
+    if hash_concat(&[left, right, note]) != id {
+      return None;
+    }
+
+    let offset = note_to_usize(note)?;
+    if dest < offset {
+      id = left.to_vec();
+      right_bound = right_bound.min(offset);
+    } else {
+      id = right.to_vec();
+      left_bound = left_bound.max(offset);
+    }
+    path = remainder;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::crypto::decode_b64_url;
+  use crate::merkle::validate_path;
+  use crate::utils::hasher;
+  use deno_core::serde_json::Value;
+
+  fn fixture() -> (Vec<u8>, Value) {
+    let data = (0..600000_usize)
+      .map(|i| ((i * 7 + 3) % 256) as u8)
+      .collect();
+    let proofs = deno_core::serde_json::from_str(include_str!(
+      "../../testdata/arweave/merkle_proofs.json"
+    ))
+    .unwrap();
+    (data, proofs)
+  }
+
+  #[test]
+  fn test_validate_path() {
+    let (data, proofs) = fixture();
+    let data_root =
+      decode_b64_url(proofs["data_root"].as_str().unwrap()).unwrap();
+
+    let mut downloaded = 0;
+    for chunk in proofs["chunks"].as_array().unwrap() {
+      let path = decode_b64_url(chunk["data_path"].as_str().unwrap()).unwrap();
+      let validated =
+        validate_path(&data_root, downloaded, data.len(), &path).unwrap();
+
+      assert_eq!(validated.left_bound, downloaded);
+      assert_eq!(
+        validated.right_bound - 1,
+        chunk["offset"].as_u64().unwrap() as usize
+      );
+      assert_eq!(
+        validated.data_hash,
+        hasher(&data[validated.left_bound..validated.right_bound])
+      );
+      downloaded = validated.right_bound;
+    }
+
+    assert_eq!(downloaded, data.len());
+  }
+
+  #[test]
+  fn test_validate_path_tampered() {
+    let (data, proofs) = fixture();
+    let data_root =
+      decode_b64_url(proofs["data_root"].as_str().unwrap()).unwrap();
+    let mut path =
+      decode_b64_url(proofs["chunks"][1]["data_path"].as_str().unwrap())
+        .unwrap();
+
+    // Proof of a chunk does not prove a different byte range.
+    assert!(validate_path(&data_root, 0, data.len(), &path)
+      .map(|validated| validated.left_bound != 0)
+      .unwrap_or(true));
+
+    let last = path.len() - 1;
+    path[last] ^= 1;
+    assert!(validate_path(&data_root, 262144, data.len(), &path).is_none());
+    assert!(validate_path(&data_root, 0, 0, &path).is_none());
+  }
+}
@@ -1,6 +1,9 @@
 use crate::arweave::TransactionData;
+use crate::errors::ContractError;
 use crate::utils::hasher;
 use deno_core::error::AnyError;
+use deno_core::serde_json::Value;
+use indexmap::map::IndexMap;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -14,6 +17,74 @@ pub enum ContractType {
   EVM,
 }
 
+/// A change of contract source requested through `canEvolve`/`evolve`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Evolution {
+  /// Sort key of the interaction after which `source_tx` took over.
+  pub sort_key: String,
+  pub interaction: String,
+  pub source_tx: String,
+  pub contract_type: ContractType,
+}
+
+/// Why an interaction was not applied.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct InteractionError {
+  /// Machine readable category, such as `execution` or `insufficient_fee`.
+  pub kind: String,
+  pub message: String,
+  /// Structured reason, for kinds that carry one.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub details: Option<Value>,
+}
+
+/// Outcome of evaluating a single interaction.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidityRecord {
+  pub valid: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<InteractionError>,
+  /// Gas used by EVM contracts, or the metered cost of WASM contracts.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub gas_used: Option<u64>,
+  /// Wall time spent evaluating the interaction, in microseconds.
+  #[serde(default)]
+  pub wall_time_us: u64,
+  #[serde(default)]
+  pub state_changed: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub result: Option<Value>,
+}
+
+pub type ValidityRecords = IndexMap<String, ValidityRecord>;
+
+impl ValidityRecord {
+  pub fn invalid(kind: &str, message: String) -> ValidityRecord {
+    ValidityRecord {
+      valid: false,
+      error: Some(InteractionError {
+        kind: kind.to_owned(),
+        message,
+        details: None,
+      }),
+      ..Default::default()
+    }
+  }
+
+  /// Entry of the plain validity table: `true` for valid interactions.
+  /// Otherwise the error message when errors are shown, or `false`. The
+  /// structured details are only kept in the record.
+  pub fn to_legacy(&self, show_errors: bool) -> Value {
+    match &self.error {
+      _ if self.valid => Value::Bool(true),
+      Some(error) if show_errors => Value::String(error.message.to_owned()),
+      _ => Value::Bool(false),
+    }
+  }
+}
+
 // impl Default for ContractType {
 //   fn default() -> ContractType {
 //     ContractType::JAVASCRIPT
@@ -33,12 +104,13 @@ pub fn get_contract_type(
   maybe_content_type: Option<String>,
   contract_transaction: &TransactionData,
   source_transaction: &TransactionData,
-) -> Result<ContractType, AnyError> {
+) -> Result<ContractType, ContractError> {
   let contract_type = maybe_content_type
     .or_else(|| source_transaction.get_tag("Content-Type").ok())
     .or_else(|| contract_transaction.get_tag("Content-Type").ok())
-    .ok_or_else(|| {
-      AnyError::msg("Contract-Src tag not found in transaction")
+    .ok_or_else(|| ContractError::MissingTag {
+      tag: String::from("Content-Type"),
+      transaction: source_transaction.id.to_owned(),
     })?;
 
   let ty = get_contract_type_raw(contract_type);
@@ -51,10 +123,13 @@ pub fn get_sort_key(
   block_id: &str,
   transaction_id: &str,
 ) -> String {
+  // Simulated interactions may carry ids that are not valid base64.
   let mut hasher_bytes =
-    base64::decode_config(block_id, base64::URL_SAFE_NO_PAD).unwrap();
+    base64::decode_config(block_id, base64::URL_SAFE_NO_PAD)
+      .unwrap_or_else(|_| block_id.as_bytes().to_vec());
   let mut tx_id =
-    base64::decode_config(transaction_id, base64::URL_SAFE_NO_PAD).unwrap();
+    base64::decode_config(transaction_id, base64::URL_SAFE_NO_PAD)
+      .unwrap_or_else(|_| transaction_id.as_bytes().to_vec());
   hasher_bytes.append(&mut tx_id);
   let hashed = hex::encode(hasher(&hasher_bytes[..]));
   let height = format!("000000{}", *block_height);
@@ -66,7 +141,10 @@ pub fn get_sort_key(
 #[cfg(test)]
 mod tests {
   use crate::arweave::{Tag, TransactionData};
-  use crate::miscellaneous::{get_contract_type, ContractType};
+  use crate::miscellaneous::{
+    get_contract_type, ContractType, InteractionError, ValidityRecord,
+  };
+  use deno_core::serde_json;
 
   #[tokio::test]
   async fn get_contract_type_test() {
@@ -114,6 +192,38 @@ mod tests {
     assert!(matches!(contract_type, ContractType::JAVASCRIPT));
   }
 
+  #[test]
+  fn validity_record_test() {
+    let valid = ValidityRecord {
+      valid: true,
+      wall_time_us: 12,
+      ..Default::default()
+    };
+    assert_eq!(valid.to_legacy(true), serde_json::json!(true));
+    assert_eq!(
+      serde_json::to_value(&valid).unwrap(),
+      serde_json::json!({ "valid": true, "wallTimeUs": 12, "stateChanged": false })
+    );
+
+    let failed = ValidityRecord::invalid("execution", String::from("boom"));
+    assert_eq!(failed.to_legacy(true), serde_json::json!("boom"));
+    assert_eq!(failed.to_legacy(false), serde_json::json!(false));
+
+    let unqualified = ValidityRecord {
+      error: Some(InteractionError {
+        kind: String::from("insufficient_fee"),
+        message: String::from("Fee too low"),
+        details: Some(serde_json::json!({ "reason": "insufficient_fee" })),
+      }),
+      ..Default::default()
+    };
+    assert_eq!(unqualified.to_legacy(false), serde_json::json!(false));
+    assert_eq!(
+      unqualified.to_legacy(true),
+      serde_json::json!("Fee too low")
+    );
+  }
+
   fn get_fake_transaction(content_type: &str) -> TransactionData {
     TransactionData {
       format: 1_usize,
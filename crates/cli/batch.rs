@@ -0,0 +1,197 @@
+use crate::run::run_output;
+use deno_core::error::AnyError;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Instant;
+use three_em_arweave::arweave::Arweave;
+use three_em_arweave::cache::ArweaveCache;
+use three_em_executor::execute_contract;
+
+/// Outcome of evaluating one contract of a batch, printed as a JSON line.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchRecord {
+  pub contract_id: String,
+  /// What `run` would print for the contract.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub state: Option<Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<BatchError>,
+  pub elapsed_ms: u64,
+}
+
+/// Why a contract could not be evaluated, with the `kind` the server
+/// reports.
+#[derive(Serialize, Debug)]
+pub struct BatchError {
+  pub kind: &'static str,
+  pub message: String,
+}
+
+/// Contract IDs listed one per line. Blank lines and lines starting with
+/// `#` are skipped.
+pub fn parse_contract_ids(source: &str) -> Vec<String> {
+  source
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .map(String::from)
+    .collect()
+}
+
+/// Reads the contract IDs of a file, or of stdin if `path` is `-`.
+pub fn read_contract_ids(path: &str) -> Result<Vec<String>, AnyError> {
+  let source = if path == "-" {
+    let mut source = String::new();
+    std::io::stdin().read_to_string(&mut source)?;
+    source
+  } else {
+    std::fs::read_to_string(path).map_err(|err| {
+      AnyError::msg(format!("Unable to read contracts file {}: {}", path, err))
+    })?
+  };
+
+  Ok(parse_contract_ids(&source))
+}
+
+async fn evaluate(
+  contract_id: String,
+  arweave: &Arweave,
+  height: Option<usize>,
+  cache: bool,
+  show_errors: bool,
+  show_validity: bool,
+) -> BatchRecord {
+  let start = Instant::now();
+  let execution = execute_contract(
+    contract_id.to_owned(),
+    height,
+    cache,
+    show_errors,
+    None,
+    None,
+    arweave,
+  )
+  .await;
+  let elapsed_ms = start.elapsed().as_millis() as u64;
+
+  match execution {
+    Ok(execution) => BatchRecord {
+      contract_id,
+      state: Some(run_output(execution, show_validity)),
+      error: None,
+      elapsed_ms,
+    },
+    Err(err) => BatchRecord {
+      contract_id,
+      state: None,
+      error: Some(BatchError {
+        kind: err.kind(),
+        message: err.to_string(),
+      }),
+      elapsed_ms,
+    },
+  }
+}
+
+/// Evaluates many contracts with one Arweave client, printing a JSON line
+/// per contract as it completes. A contract failing does not stop the
+/// others, but makes the batch return an error at the end.
+///
+/// V8 isolates of a thread have to be dropped in the reverse order they
+/// were created, so contracts are not interleaved on a thread. Instead,
+/// `concurrency` threads evaluate one contract at a time on `runtime`.
+#[allow(clippy::too_many_arguments)]
+pub fn run_batch(
+  runtime: &tokio::runtime::Handle,
+  port: i32,
+  host: String,
+  protocol: String,
+  contract_ids: Vec<String>,
+  concurrency: usize,
+  show_validity: bool,
+  height: Option<usize>,
+  no_cache: bool,
+  show_errors: bool,
+  verify_signatures: bool,
+  verify_data: bool,
+  enforce_requirements: bool,
+  heap_limit: Option<usize>,
+) -> Result<(), AnyError> {
+  let arweave = Arweave::new(port, host, protocol, ArweaveCache::new())
+    .with_signature_verification(verify_signatures)
+    .with_data_verification(verify_data)
+    .with_requirement_enforcement(enforce_requirements)
+    .with_heap_limit(heap_limit);
+  let total = contract_ids.len();
+  let queue = Mutex::new(contract_ids.into_iter());
+  let (sender, receiver) = mpsc::channel();
+
+  let failed = std::thread::scope(|scope| {
+    for _ in 0..concurrency.clamp(1, total.max(1)) {
+      let sender = sender.clone();
+      let (arweave, queue) = (&arweave, &queue);
+      scope.spawn(move || loop {
+        let contract_id = match queue.lock().unwrap().next() {
+          Some(contract_id) => contract_id,
+          None => break,
+        };
+        let record = runtime.block_on(evaluate(
+          contract_id,
+          arweave,
+          height,
+          !no_cache,
+          show_errors,
+          show_validity,
+        ));
+        if sender.send(record).is_err() {
+          break;
+        }
+      });
+    }
+    drop(sender);
+
+    let mut failed = 0;
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for record in receiver {
+      if record.error.is_some() {
+        failed += 1;
+      }
+      writeln!(stdout, "{}", serde_json::to_string(&record)?)?;
+      stdout.flush()?;
+    }
+    Ok::<usize, AnyError>(failed)
+  })?;
+
+  if failed > 0 {
+    return Err(AnyError::msg(format!(
+      "{} of {} contracts could not be evaluated",
+      failed, total
+    )));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::batch::parse_contract_ids;
+
+  #[test]
+  fn test_parse_contract_ids() {
+    let source = "# Tokens\nt9T7DIOGxx4VWXoCEeYYarFYeERTpWIC1V3y-BPZgKE\n\n  \
+                  KfU_1Uxe3-h2r3tP6ZMfMT-HBFlM887tTFtS-p4edYQ  \n#done\n";
+    assert_eq!(
+      parse_contract_ids(source),
+      vec![
+        "t9T7DIOGxx4VWXoCEeYYarFYeERTpWIC1V3y-BPZgKE",
+        "KfU_1Uxe3-h2r3tP6ZMfMT-HBFlM887tTFtS-p4edYQ",
+      ]
+    );
+    assert!(parse_contract_ids("\n# nothing\n").is_empty());
+  }
+}
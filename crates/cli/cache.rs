@@ -0,0 +1,409 @@
+use crate::cli::config::Config;
+use crate::cli::parse::CacheCommand;
+use crate::output::write_value;
+use deno_core::error::AnyError;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind};
+use std::path::{Component, Path};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tar::EntryType;
+use three_em_arweave::arweave::LoadedContract;
+use three_em_arweave::cache::{ArweaveCache, CacheExt, CacheKind, StateResult};
+use three_em_arweave::gql_result::GQLEdgeInterface;
+
+/// Files cached for a contract.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContract {
+  pub id: String,
+  pub files: Vec<CacheKind>,
+  /// Size of the files, in bytes.
+  pub size: u64,
+  /// Last time one of the files was written, in seconds since the Unix
+  /// epoch.
+  pub modified: u64,
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_secs())
+    .unwrap_or_default()
+}
+
+pub fn list(cache: &ArweaveCache) -> Result<Vec<CachedContract>, AnyError> {
+  let mut contracts: Vec<CachedContract> = Vec::new();
+
+  // Files come sorted by contract.
+  for file in cache.files()? {
+    let modified = unix_seconds(file.modified);
+    match contracts.last_mut() {
+      Some(contract) if contract.id == file.contract_id => {
+        contract.files.push(file.kind);
+        contract.size += file.size;
+        contract.modified = contract.modified.max(modified);
+      }
+      _ => contracts.push(CachedContract {
+        id: file.contract_id,
+        files: vec![file.kind],
+        size: file.size,
+        modified,
+      }),
+    }
+  }
+
+  Ok(contracts)
+}
+
+fn read_cache_file<T: DeserializeOwned>(
+  cache: &ArweaveCache,
+  kind: CacheKind,
+  contract_id: &str,
+) -> Result<Option<T>, AnyError> {
+  let path = cache.folder(kind).join(kind.file_name(contract_id));
+  match File::open(&path) {
+    Ok(file) => serde_json::from_reader(BufReader::new(file))
+      .map(Some)
+      .map_err(|err| {
+        AnyError::msg(format!(
+          "Unable to parse cache file {}: {}",
+          path.display(),
+          err
+        ))
+      }),
+    Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+    Err(err) => Err(err.into()),
+  }
+}
+
+/// Summary of what is cached for a contract.
+pub fn inspect(
+  cache: &ArweaveCache,
+  contract_id: &str,
+) -> Result<Value, AnyError> {
+  let contract: Option<LoadedContract> =
+    read_cache_file(cache, CacheKind::Contract, contract_id)?;
+  let interactions: Option<Vec<GQLEdgeInterface>> =
+    read_cache_file(cache, CacheKind::Interactions, contract_id)?;
+  let state: Option<StateResult> =
+    read_cache_file(cache, CacheKind::State, contract_id)?;
+
+  if contract.is_none() && interactions.is_none() && state.is_none() {
+    return Err(AnyError::msg(format!(
+      "Nothing is cached for contract {}",
+      contract_id
+    )));
+  }
+
+  Ok(serde_json::json!({
+    "id": contract_id,
+    "contract": contract.map(|contract| serde_json::json!({
+      "sourceTxId": contract.contract_src_tx_id,
+      "contractType": contract.contract_type,
+      "sourceSize": contract.contract_src.len(),
+    })),
+    "interactions": interactions.map(|interactions| serde_json::json!({
+      "count": interactions.len(),
+      "firstHeight": interactions.first().map(|edge| edge.node.block.height),
+      "lastHeight": interactions.last().map(|edge| edge.node.block.height),
+      "lastId": interactions.last().map(|edge| edge.node.id.to_owned()),
+    })),
+    "state": state.map(|state| serde_json::json!({
+      "evaluated": state.validity.len(),
+      "valid": state
+        .validity
+        .values()
+        .filter(|valid| **valid == Value::Bool(true))
+        .count(),
+      "evolutions": state.evolutions.len(),
+      "state": state.state,
+    })),
+  }))
+}
+
+/// Removes every cached file, or those of `contract_id`. Returns how many
+/// files were removed.
+pub fn clear(
+  cache: &ArweaveCache,
+  contract_id: Option<&str>,
+) -> Result<usize, AnyError> {
+  let files: Vec<_> = cache
+    .files()?
+    .into_iter()
+    .filter(|file| contract_id.map_or(true, |id| file.contract_id == id))
+    .collect();
+  cache.remove_files(&files)?;
+  Ok(files.len())
+}
+
+/// Removes the cached files last written more than `older_than` ago.
+pub fn prune(
+  cache: &ArweaveCache,
+  older_than: Duration,
+) -> Result<usize, AnyError> {
+  let cutoff = match SystemTime::now().checked_sub(older_than) {
+    Some(cutoff) => cutoff,
+    None => return Ok(0),
+  };
+  let files: Vec<_> = cache
+    .files()?
+    .into_iter()
+    .filter(|file| file.modified <= cutoff)
+    .collect();
+  cache.remove_files(&files)?;
+  Ok(files.len())
+}
+
+/// Writes every cached file to a gzipped tarball, under the folder of its
+/// kind.
+pub fn export(cache: &ArweaveCache, path: &Path) -> Result<usize, AnyError> {
+  let files = cache.files()?;
+  let mut archive = tar::Builder::new(GzEncoder::new(
+    File::create(path)?,
+    Compression::default(),
+  ));
+
+  for file in &files {
+    let name = Path::new(file.kind.folder())
+      .join(file.kind.file_name(&file.contract_id));
+    archive.append_path_with_name(&file.path, name)?;
+  }
+  archive.into_inner()?.finish()?;
+
+  Ok(files.len())
+}
+
+// Only `<folder>/<file>` entries naming a file the cache writes are
+// accepted, which also keeps entries from being unpacked outside of it.
+fn archive_entry(path: &Path) -> Option<(CacheKind, String)> {
+  let mut components = path.components().map(|component| match component {
+    Component::Normal(name) => name.to_str(),
+    _ => None,
+  });
+  let kind = CacheKind::from_folder(components.next()??)?;
+  let name = components.next()??;
+  if components.next().is_some() || kind.contract_id(name).is_none() {
+    return None;
+  }
+  Some((kind, name.to_owned()))
+}
+
+/// Adds the files of a tarball written by [`export`] to the cache,
+/// replacing those of the same contracts.
+pub fn import(cache: &ArweaveCache, path: &Path) -> Result<usize, AnyError> {
+  let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+  let mut imported = 0;
+
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+    let entry_path = entry.path()?.into_owned();
+    let unexpected = || {
+      AnyError::msg(format!(
+        "Unexpected file in cache archive: {}",
+        entry_path.display()
+      ))
+    };
+
+    // Links and special files are rejected rather than unpacked, so an
+    // archive cannot make the cache point outside of it.
+    match entry.header().entry_type() {
+      EntryType::Directory => continue,
+      EntryType::Regular => {}
+      _ => return Err(unexpected()),
+    }
+
+    let (kind, name) = archive_entry(&entry_path).ok_or_else(unexpected)?;
+    let mut file = File::create(cache.folder(kind).join(name))?;
+    std::io::copy(&mut entry, &mut file)?;
+    imported += 1;
+  }
+
+  Ok(imported)
+}
+
+pub fn cache(command: CacheCommand, config: &Config) -> Result<(), AnyError> {
+  let cache = ArweaveCache::new();
+
+  match command {
+    CacheCommand::List { output } => {
+      write_value(
+        std::io::stdout().lock(),
+        &serde_json::to_value(list(&cache)?)?,
+        output.resolve(config),
+      )?;
+    }
+    CacheCommand::Inspect {
+      contract_id,
+      output,
+    } => {
+      write_value(
+        std::io::stdout().lock(),
+        &inspect(&cache, &contract_id)?,
+        output.resolve(config),
+      )?;
+    }
+    CacheCommand::Clear { contract } => {
+      let removed = clear(&cache, contract.as_deref())?;
+      println!("Removed {} file(s)", removed);
+    }
+    CacheCommand::Prune { older_than } => {
+      let removed = prune(&cache, older_than)?;
+      println!("Removed {} file(s)", removed);
+    }
+    CacheCommand::Export { path } => {
+      let exported = export(&cache, &path)?;
+      println!("Exported {} file(s) to {}", exported, path.display());
+    }
+    CacheCommand::Import { path } => {
+      let imported = import(&cache, &path)?;
+      println!("Imported {} file(s) from {}", imported, path.display());
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cache::{
+    archive_entry, clear, export, import, inspect, list, prune,
+  };
+  use flate2::write::GzEncoder;
+  use flate2::Compression;
+  use indexmap::map::IndexMap;
+  use std::fs::File;
+  use std::path::Path;
+  use std::time::Duration;
+  use tar::EntryType;
+  use three_em_arweave::arweave::LoadedContract;
+  use three_em_arweave::cache::{
+    ArweaveCache, CacheExt, CacheKind, StateResult,
+  };
+  use three_em_arweave::miscellaneous::ContractType;
+  use three_em_executor::test_util::{
+    generate_fake_interaction, generate_fake_loaded_contract_data,
+  };
+
+  fn cache_contract(cache: &mut ArweaveCache, id: &str) {
+    cache.cache_contract(&LoadedContract {
+      id: String::from(id),
+      ..generate_fake_loaded_contract_data(
+        b"export function handle() {}",
+        ContractType::JAVASCRIPT,
+        String::from("{}"),
+      )
+    });
+  }
+
+  #[test]
+  fn test_cache_management() {
+    let root = std::env::temp_dir()
+      .join(format!("three_em_cache_test_{}", std::process::id()));
+    let mut cache = ArweaveCache::at(&root);
+
+    cache_contract(&mut cache, "contract-a");
+    cache.cache_interactions(
+      String::from("contract-a"),
+      &[generate_fake_interaction(
+        serde_json::json!({}),
+        "tx1",
+        None,
+        Some(10),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+      )],
+    );
+    let mut validity = IndexMap::new();
+    validity.insert(String::from("tx1"), serde_json::json!(true));
+    cache.cache_states(
+      String::from("contract-a"),
+      StateResult {
+        state: serde_json::json!({ "count": 1 }),
+        validity,
+        evolutions: Vec::new(),
+        requests: Default::default(),
+        records: Default::default(),
+      },
+    );
+    cache_contract(&mut cache, "contract-b");
+
+    let contracts = list(&cache).unwrap();
+    assert_eq!(contracts.len(), 2);
+    assert_eq!(contracts[0].id, "contract-a");
+    assert_eq!(
+      contracts[0].files,
+      vec![
+        CacheKind::Contract,
+        CacheKind::Interactions,
+        CacheKind::State
+      ]
+    );
+
+    let summary = inspect(&cache, "contract-a").unwrap();
+    assert_eq!(summary["interactions"]["lastHeight"], 10);
+    assert_eq!(summary["state"]["valid"], 1);
+    assert_eq!(summary["state"]["state"], serde_json::json!({ "count": 1 }));
+    assert!(summary["contract"].is_object());
+    assert!(inspect(&cache, "contract-c").is_err());
+
+    let archive = root.join("cache.tar.gz");
+    assert_eq!(export(&cache, &archive).unwrap(), 4);
+    assert_eq!(clear(&cache, Some("contract-b")).unwrap(), 1);
+    assert_eq!(prune(&cache, Duration::from_secs(3600)).unwrap(), 0);
+    assert_eq!(prune(&cache, Duration::ZERO).unwrap(), 3);
+    assert!(list(&cache).unwrap().is_empty());
+
+    assert_eq!(import(&cache, &archive).unwrap(), 4);
+    assert_eq!(list(&cache).unwrap().len(), 2);
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn test_import_links() {
+    let root = std::env::temp_dir()
+      .join(format!("three_em_cache_links_test_{}", std::process::id()));
+    let cache = ArweaveCache::at(&root);
+
+    let archive = root.join("links.tar.gz");
+    let mut builder = tar::Builder::new(GzEncoder::new(
+      File::create(&archive).unwrap(),
+      Compression::default(),
+    ));
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(EntryType::Symlink);
+    header.set_size(0);
+    builder
+      .append_link(&mut header, "contracts/contract-a.json", "/etc/passwd")
+      .unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+
+    assert!(import(&cache, &archive).is_err());
+    assert!(list(&cache).unwrap().is_empty());
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn test_archive_entry() {
+    assert_eq!(
+      archive_entry(Path::new("states/contract-a_result.json")),
+      Some((CacheKind::State, String::from("contract-a_result.json")))
+    );
+    assert!(archive_entry(Path::new("states/contract-a.json")).is_none());
+    assert!(archive_entry(Path::new("keys/contract-a.json")).is_none());
+    assert!(archive_entry(Path::new("contracts/../../a.json")).is_none());
+    assert!(archive_entry(Path::new("/contracts/a.json")).is_none());
+    assert!(archive_entry(Path::new("contracts/a/b.json")).is_none());
+  }
+}
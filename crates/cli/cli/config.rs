@@ -0,0 +1,85 @@
+use crate::cli::parse::{OutputFormat, Protocol};
+use deno_core::error::AnyError;
+use serde::Deserialize;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Config file read from the working directory when `--config` is not given.
+pub const CONFIG_FILE: &str = "3em.toml";
+
+/// Defaults read from a config file. Flags and `THREE_EM_*` environment
+/// variables take precedence over them.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+  pub host: Option<String>,
+  pub port: Option<u16>,
+  pub protocol: Option<Protocol>,
+  pub cache_dir: Option<PathBuf>,
+  /// Maximum heap size of JavaScript contracts, in megabytes.
+  pub heap_limit: Option<u64>,
+  pub output: Option<OutputFormat>,
+  /// Contracts kept evaluated by `serve`, along with those of `--track`.
+  pub track: Option<Vec<String>>,
+}
+
+impl Config {
+  /// Reads the config file at `path`, or `3em.toml` if no path is given.
+  /// Only the latter may be missing.
+  pub fn load(path: Option<&Path>) -> Result<Config, AnyError> {
+    let (path, required) = match path {
+      Some(path) => (path, true),
+      None => (Path::new(CONFIG_FILE), false),
+    };
+
+    let source = match std::fs::read_to_string(path) {
+      Ok(source) => source,
+      Err(err) if !required && err.kind() == ErrorKind::NotFound => {
+        return Ok(Config::default())
+      }
+      Err(err) => {
+        return Err(AnyError::msg(format!(
+          "Unable to read config file {}: {}",
+          path.display(),
+          err
+        )))
+      }
+    };
+
+    Config::parse(&source).map_err(|err| {
+      AnyError::msg(format!(
+        "Unable to parse config file {}: {}",
+        path.display(),
+        err
+      ))
+    })
+  }
+
+  pub fn parse(source: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(source)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cli::config::Config;
+  use crate::cli::parse::Protocol;
+  use std::path::Path;
+
+  #[test]
+  fn test_load_config() {
+    assert_eq!(Config::load(None).unwrap(), Config::default());
+    assert!(Config::load(Some(Path::new("missing.toml"))).is_err());
+
+    let config = Config::parse("port = 1984\nprotocol = \"http\"").unwrap();
+    assert_eq!(config.port, Some(1984));
+    assert_eq!(config.protocol, Some(Protocol::Http));
+
+    let config = Config::parse("track = [\"contract-a\"]").unwrap();
+    assert_eq!(config.track, Some(vec![String::from("contract-a")]));
+
+    // Typos are reported instead of being ignored.
+    assert!(Config::parse("hots = \"localhost\"").is_err());
+    assert!(Config::parse("port = 100000").is_err());
+  }
+}
@@ -1,143 +1,692 @@
 /**
  *
  * @Purpose: Parse cmd arguments so main.rs has data to execute other libs.
- *           Parse Command Arguments and determine whether cmd is 'Run', 'DryRun' or 'Serve'.
- *           Upon Match, grab the rest of the data from 'pargs' and feed it into the 'Flags' enum.
- *           ParseResult enum which houses the Flags enum is then returned inside Ok()
+ *           Subcommands and their options are declared on the `Flags` enum,
+ *           which clap turns into a parser along with its help and errors.
  *
- * @Note: Code to understand right away
- * `subcommand()` - grabs first argument in cmd i.e. 'Run', 'DryRun' or 'Serve'
- * `opt_value_from_str(key)` - sees if an optional key was provided & extracts value, ex. [--height, 42] -> 42
- * `unwrap_or_else()` - extract value or do something else, ex. Option<String> becomes String
- * `as_deref()` - converts an Option<String> into Option<&str> so we can borrow and not clone any values, more efficient.
+ * @Note: Options that are not given fall back, in order, to their `THREE_EM_*`
+ *        environment variable, the `3em.toml` config file and a default.
+ *        `resolve()` applies the last two.
  *
  */
-use crate::print_help::print_help;
-use pico_args::Arguments;
-use std::ops::Deref;
+use crate::cli::config::Config;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use deno_core::error::AnyError;
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug)]
+/// A blazingly fast, secure, reliable, multi-language execution machine for
+/// Smart Contracts.
+#[derive(Parser, Debug)]
+#[command(name = "three_em", version)]
+pub struct Cli {
+  /// Path to the config file [default: 3em.toml when present]
+  #[arg(long, global = true, env = "THREE_EM_CONFIG")]
+  pub config: Option<PathBuf>,
+  /// Folder where contracts, interactions and states are cached [default: 3em in the user's cache folder]
+  #[arg(long, global = true, env = "THREE_EM_CACHE_DIR")]
+  pub cache_dir: Option<PathBuf>,
+  #[command(subcommand)]
+  pub flags: Flags,
+}
+
+#[derive(Subcommand, Debug)]
 pub enum Flags {
+  /// Runs a contract deployed to the Arweave network.
   Run {
-    host: String,
-    port: i32,
-    protocol: String,
+    /// ID of contract to be evaluated
+    #[arg(long = "contract-id", required_unless_present = "contracts_file")]
     tx: Option<String>,
-    pretty_print: bool,
+    /// Path to file listing the IDs of contracts to be evaluated, one per line, or `-` for stdin. Prints a JSON line per contract
+    #[arg(
+      long,
+      conflicts_with_all = [
+        "tx", "save", "benchmark", "no_print", "trace", "select", "output",
+        "pretty_print"
+      ]
+    )]
+    contracts_file: Option<String>,
+    /// Number of contracts of a contracts file evaluated at once
+    #[arg(
+      long,
+      default_value_t = 4,
+      value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    concurrency: u16,
+    #[command(flatten)]
+    gateway: GatewayArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// JSON pointer (`/balances/abc`) or JSON path (`$.balances.abc`) of the part of the output to be printed
+    #[arg(long)]
+    select: Option<String>,
+    /// Whether no output should be displayed
+    #[arg(long)]
     no_print: bool,
+    /// Whether validity table, interaction records and evolution history should be included in output
+    #[arg(long)]
     show_validity: bool,
-    save: bool,
+    /// Path to file where output will be saved
+    #[arg(long)]
+    save: Option<String>,
+    /// Whether execution time should be displayed
+    #[arg(long)]
     benchmark: bool,
-    save_path: String,
+    /// Maximum height to be evaluated
+    #[arg(long)]
     height: Option<usize>,
+    /// Whether the cache system should be skipped during evaluation
+    #[arg(long)]
     no_cache: bool,
+    /// Whether exceptions thrown during evaluation should be shown
+    #[arg(long)]
     show_errors: bool,
+    /// Whether contract and interaction signatures should be verified
+    #[arg(long)]
+    verify_signatures: bool,
+    /// Whether contract sources and init states should be verified against their data_root
+    #[arg(long)]
+    verify_data: bool,
+    /// Whether interactions should meet the contract's Min-Fee, Interaction-Target and Min-Quantity tags
+    #[arg(long)]
+    enforce_requirements: bool,
+    /// Path to file where the state change of every interaction will be written as JSON Lines. Evaluates from the init state
+    #[arg(long)]
+    trace: Option<String>,
+    #[command(flatten)]
+    limits: LimitArgs,
   },
+  /// Runs a local contract with local interactions provided in a configuration file.
   DryRun {
-    host: String,
-    port: i32,
-    protocol: String,
-    pretty_print: bool,
+    /// Path to configuration file to be used
+    #[arg(long)]
+    file: String,
+    #[command(flatten)]
+    gateway: GatewayArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Whether validity table and interaction records should be included in output
+    #[arg(long)]
     show_validity: bool,
-    file: Option<String>,
+    /// Path to file where the state change of every interaction will be written as JSON Lines
+    #[arg(long)]
+    trace: Option<String>,
+    /// Whether interactions should be stepped through one at a time
+    #[arg(long, conflicts_with = "watch")]
+    interactive: bool,
+    /// Whether network access should be served from the fixtures of the configuration file
+    #[arg(long)]
+    offline: bool,
+    /// Whether the configuration file and contract source should be watched, printing the changes of every evaluation
+    #[arg(long)]
+    watch: bool,
+    #[command(flatten)]
+    limits: LimitArgs,
+  },
+  /// Evaluates a contract at two heights and prints a JSON Patch (RFC 6902) from the first state to the second, along with the interactions in between and their validity.
+  Diff {
+    /// ID of contract to be evaluated
+    #[arg(long = "contract-id")]
+    tx: String,
+    /// Height of the original state
+    #[arg(long)]
+    from: usize,
+    /// Height of the final state
+    #[arg(long)]
+    to: usize,
+    #[command(flatten)]
+    gateway: GatewayArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Whether the cache system should be skipped during evaluation
+    #[arg(long)]
+    no_cache: bool,
+    /// Whether exceptions thrown during evaluation should be shown
+    #[arg(long)]
+    show_errors: bool,
+    #[command(flatten)]
+    limits: LimitArgs,
   },
+  /// Creates a server with an API to evaluate contracts.
   Serve {
-    server_host: String,
+    /// Host to be used by the server
+    #[arg(long = "host", default_value = "127.0.0.1")]
+    server_host: IpAddr,
+    /// Port to be used by the server
+    #[arg(long = "port", default_value_t = 5400)]
     server_port: u16,
+    /// ID of a contract to be kept evaluated in the background, synced through the gateway of THREE_EM_HOST, THREE_EM_PORT and THREE_EM_PROTOCOL or the config file. May be repeated
+    #[arg(long = "track")]
+    tracked: Vec<String>,
+    /// How often tracked contracts are synced, such as 30s or 5m
+    #[arg(long, value_parser = parse_duration, default_value = "30s")]
+    sync_interval: Duration,
+  },
+  /// Manages the cache of contracts, interactions and states.
+  Cache {
+    #[command(subcommand)]
+    command: CacheCommand,
+  },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+  /// Lists the cached contracts.
+  List {
+    #[command(flatten)]
+    output: OutputArgs,
+  },
+  /// Describes what is cached for a contract.
+  Inspect {
+    /// ID of the contract
+    contract_id: String,
+    #[command(flatten)]
+    output: OutputArgs,
+  },
+  /// Removes every cached file, or those of a single contract.
+  Clear {
+    /// ID of the contract whose files should be removed
+    #[arg(long)]
+    contract: Option<String>,
+  },
+  /// Removes the cached files that were not updated for a while.
+  Prune {
+    /// Age of the files to be removed, such as 30d, 12h, 45m or 90s
+    #[arg(long, value_parser = parse_duration)]
+    older_than: Duration,
   },
+  /// Writes every cached file to a gzipped tarball.
+  Export {
+    /// Path of the tarball
+    path: PathBuf,
+  },
+  /// Adds the files of a tarball written by `cache export` to the cache.
+  Import {
+    /// Path of the tarball
+    path: PathBuf,
+  },
+}
+
+/// Parses a number of days, hours, minutes or seconds, such as `30d`.
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+  let invalid = || {
+    format!(
+      "invalid duration `{}`, expected a number followed by d, h, m or s",
+      duration
+    )
+  };
+  let unit = duration.chars().last().ok_or_else(invalid)?;
+  let seconds = match unit {
+    'd' => 24 * 60 * 60,
+    'h' => 60 * 60,
+    'm' => 60,
+    's' => 1,
+    _ => return Err(invalid()),
+  };
+  let amount: u64 = duration[..duration.len() - 1]
+    .parse()
+    .map_err(|_| invalid())?;
+  amount
+    .checked_mul(seconds)
+    .map(Duration::from_secs)
+    .ok_or_else(invalid)
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+  Http,
+  Https,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+  /// JSON on a single line
+  #[default]
+  Json,
+  /// Prettified JSON
+  Pretty,
+  /// JSON Lines, with an array printed one element per line
+  Jsonl,
+  /// CBOR (RFC 8949)
+  Cbor,
+  /// MessagePack
+  Msgpack,
+  /// Decoded storage of EVM contracts
+  Table,
+}
+
+#[derive(Args, Debug)]
+pub struct GatewayArgs {
+  /// Gateway url to be used by Executor & SmartWeave APIs [default: arweave.net]
+  #[arg(long, env = "THREE_EM_HOST")]
+  pub host: Option<String>,
+  /// Gateway port to be used [default: 443]
+  #[arg(long, env = "THREE_EM_PORT")]
+  pub port: Option<u16>,
+  /// Protocol to be used for gateway communication [default: https]
+  #[arg(long, value_enum, env = "THREE_EM_PROTOCOL")]
+  pub protocol: Option<Protocol>,
+}
+
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+  /// Format of the output [default: json]
+  #[arg(long, value_enum, env = "THREE_EM_OUTPUT")]
+  pub output: Option<OutputFormat>,
+  /// Shorthand for `--output pretty`
+  #[arg(long)]
+  pub pretty_print: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct LimitArgs {
+  /// Maximum heap size of JavaScript contracts, in megabytes [default: 200]
+  #[arg(long, env = "THREE_EM_HEAP_LIMIT", value_parser = clap::value_parser!(u64).range(1..))]
+  pub heap_limit: Option<u64>,
+}
+
+/// Gateway options once defaults are applied.
+#[derive(Debug, PartialEq)]
+pub struct Gateway {
+  pub host: String,
+  pub port: i32,
+  pub protocol: String,
+}
+
+impl GatewayArgs {
+  pub fn resolve(self, config: &Config) -> Gateway {
+    let protocol = self.protocol.or(config.protocol).unwrap_or(Protocol::Https);
+    Gateway {
+      host: self
+        .host
+        .or_else(|| config.host.to_owned())
+        .unwrap_or_else(|| String::from("arweave.net")),
+      port: self.port.or(config.port).unwrap_or(443) as i32,
+      protocol: String::from(match protocol {
+        Protocol::Http => "http",
+        Protocol::Https => "https",
+      }),
+    }
+  }
+}
+
+impl Gateway {
+  /// Gateway of the `THREE_EM_*` environment variables and the config file,
+  /// for commands without gateway options.
+  pub fn from_env(config: &Config) -> Result<Gateway, AnyError> {
+    Gateway::from_vars(config, |name| std::env::var(name).ok())
+  }
+
+  fn from_vars<F: Fn(&str) -> Option<String>>(
+    config: &Config,
+    var: F,
+  ) -> Result<Gateway, AnyError> {
+    let invalid = |name: &str, value: &str| {
+      AnyError::msg(format!("Invalid value for {}: {}", name, value))
+    };
+    let port = var("THREE_EM_PORT")
+      .map(|port| port.parse().map_err(|_| invalid("THREE_EM_PORT", &port)))
+      .transpose()?;
+    let protocol = var("THREE_EM_PROTOCOL")
+      .map(|protocol| {
+        Protocol::from_str(&protocol, false)
+          .map_err(|_| invalid("THREE_EM_PROTOCOL", &protocol))
+      })
+      .transpose()?;
+
+    Ok(
+      GatewayArgs {
+        host: var("THREE_EM_HOST"),
+        port,
+        protocol,
+      }
+      .resolve(config),
+    )
+  }
 }
 
-#[derive(Debug)]
-pub enum ParseResult {
-  Help { cmd: String },
-  Known { flag: Flags },
+impl OutputArgs {
+  pub fn resolve(self, config: &Config) -> OutputFormat {
+    if self.pretty_print {
+      return OutputFormat::Pretty;
+    }
+    self.output.or(config.output).unwrap_or_default()
+  }
 }
 
-fn parse_node_limit(
-  arguments: &mut Arguments,
-) -> Result<i32, pico_args::Error> {
-  let node_limit = arguments.opt_value_from_str("--node-limit")?.unwrap_or(8);
-  if node_limit < 8 {
-    panic!("At least 8 nodes are needed.");
+impl LimitArgs {
+  pub fn resolve(self, config: &Config) -> Option<usize> {
+    self
+      .heap_limit
+      .or(config.heap_limit)
+      .map(|heap_limit| heap_limit as usize)
   }
-  Ok(node_limit)
 }
 
-pub fn parse() -> Result<ParseResult, pico_args::Error> {
-  let mut pargs = Arguments::from_env(); //Ex. -> Arguments(["arg1", "arg2", "arg3"])
-  let is_help = pargs.contains("--help"); //Checks if user entered help flag
-
-  /**
-   * subcommand -> Ok(Some("arg1")) grabs first argument
-   * as_deref -> Peels back the Ok() wrapper so its Some("arg1")
-   * unwrap_or -> Peels back <Some> or panics
-   * to_string -> Converts back to mutable string
-   */
-  let cmd = pargs
-    .subcommand()?
-    .as_deref()
-    .unwrap_or("Unknown")
-    .to_string();
-
-  if is_help {
-    //Store cmd with --help flag inside Help struct
-    Ok(ParseResult::Help {
-      cmd: String::from(cmd),
-    })
-  } else {
-    //CHECK IF subcommand was dry-run, run or serve
-    #[allow(clippy::wildcard_in_or_patterns)]
-    let flags = match cmd.deref() {
-      "dry-run" => ParseResult::Known {
-        flag: Flags::DryRun {
-          host: pargs
-            .opt_value_from_str("--host")?
-            .unwrap_or_else(|| String::from("arweave.net")),
-          port: pargs.opt_value_from_str("--port")?.unwrap_or(80),
-          protocol: pargs
-            .opt_value_from_str("--protocol")?
-            .unwrap_or_else(|| String::from("https")),
-          pretty_print: pargs.contains("--pretty-print"),
-          show_validity: pargs.contains("--show-validity"),
-          file: pargs.opt_value_from_str("--file").unwrap(),
-        },
-      },
-      "run" => ParseResult::Known {
-        flag: Flags::Run {
-          host: pargs
-            .opt_value_from_str("--host")?
-            .unwrap_or_else(|| String::from("arweave.net")),
-          port: pargs.opt_value_from_str("--port")?.unwrap_or(80),
-          protocol: pargs
-            .opt_value_from_str("--protocol")?
-            .unwrap_or_else(|| String::from("https")),
-          tx: pargs.opt_value_from_str("--contract-id").unwrap(),
-          pretty_print: pargs.contains("--pretty-print"),
-          no_print: pargs.contains("--no-print"),
-          show_validity: pargs.contains("--show-validity"),
-          save: pargs.contains("--save"),
-          benchmark: pargs.contains("--benchmark"),
-          save_path: pargs
-            .opt_value_from_str("--save")?
-            .unwrap_or_else(|| String::from("")),
-          height: { pargs.opt_value_from_str("--height").unwrap() },
-          no_cache: pargs.contains("--no-cache"),
-          show_errors: pargs.contains("--show-errors"),
-        },
-      },
-      "serve" => ParseResult::Known {
-        flag: Flags::Serve {
-          server_host: pargs
-            .opt_value_from_str("--host")?
-            .unwrap_or_else(|| String::from("127.0.0.1")),
-          server_port: pargs.opt_value_from_str("--port")?.unwrap_or(5400),
-        },
-      },
-      "Unknown" | _ => ParseResult::Help {
-        cmd: String::from("none"),
-      },
+#[cfg(test)]
+mod tests {
+  use crate::cli::config::Config;
+  use crate::cli::parse::{
+    parse_duration, CacheCommand, Cli, Flags, Gateway, OutputFormat,
+  };
+  use clap::{CommandFactory, Parser};
+  use std::time::Duration;
+
+  #[test]
+  fn test_cli() {
+    Cli::command().debug_assert();
+  }
+
+  #[test]
+  fn test_parse_run() {
+    let cli = Cli::try_parse_from([
+      "three_em",
+      "run",
+      "--contract-id",
+      "t9T7DIOGxx4VWXoCEeYYarFYeERTpWIC1V3y-BPZgKE",
+      "--save",
+      "state.json",
+      "--port",
+      "1984",
+    ])
+    .unwrap();
+
+    match cli.flags {
+      Flags::Run {
+        tx, gateway, save, ..
+      } => {
+        assert_eq!(
+          tx.as_deref(),
+          Some("t9T7DIOGxx4VWXoCEeYYarFYeERTpWIC1V3y-BPZgKE")
+        );
+        assert_eq!(save.as_deref(), Some("state.json"));
+        assert_eq!(
+          gateway.resolve(&Config::default()),
+          Gateway {
+            host: String::from("arweave.net"),
+            port: 1984,
+            protocol: String::from("https"),
+          }
+        );
+      }
+      flags => panic!("Unexpected flags: {:?}", flags),
+    }
+  }
+
+  #[test]
+  fn test_parse_run_batch() {
+    let cli = Cli::try_parse_from([
+      "three_em",
+      "run",
+      "--contracts-file",
+      "-",
+      "--concurrency",
+      "8",
+    ])
+    .unwrap();
+
+    match cli.flags {
+      Flags::Run {
+        tx,
+        contracts_file,
+        concurrency,
+        ..
+      } => {
+        assert_eq!(tx, None);
+        assert_eq!(contracts_file.as_deref(), Some("-"));
+        assert_eq!(concurrency, 8);
+      }
+      flags => panic!("Unexpected flags: {:?}", flags),
+    }
+
+    assert!(Cli::try_parse_from([
+      "three_em",
+      "run",
+      "--contracts-file",
+      "ids.txt",
+      "--contract-id",
+      "id",
+    ])
+    .is_err());
+    // Batch results are always printed as JSON lines.
+    assert!(Cli::try_parse_from([
+      "three_em",
+      "run",
+      "--contracts-file",
+      "ids.txt",
+      "--output",
+      "cbor",
+    ])
+    .is_err());
+    assert!(Cli::try_parse_from([
+      "three_em",
+      "run",
+      "--contracts-file",
+      "ids.txt",
+      "--pretty-print",
+    ])
+    .is_err());
+    assert!(Cli::try_parse_from([
+      "three_em",
+      "run",
+      "--contracts-file",
+      "ids.txt",
+      "--concurrency",
+      "0",
+    ])
+    .is_err());
+  }
+
+  #[test]
+  fn test_parse_serve() {
+    let cli = Cli::try_parse_from([
+      "three_em",
+      "serve",
+      "--track",
+      "contract-a",
+      "--track",
+      "contract-b",
+      "--sync-interval",
+      "5m",
+    ])
+    .unwrap();
+
+    match cli.flags {
+      Flags::Serve {
+        server_port,
+        tracked,
+        sync_interval,
+        ..
+      } => {
+        assert_eq!(server_port, 5400);
+        assert_eq!(tracked, vec!["contract-a", "contract-b"]);
+        assert_eq!(sync_interval, Duration::from_secs(300));
+      }
+      flags => panic!("Unexpected flags: {:?}", flags),
+    }
+  }
+
+  #[test]
+  fn test_gateway_from_vars() {
+    let config = Config::parse("host = \"localhost\"\nport = 1984").unwrap();
+    let vars = |vars: &'static [(&'static str, &'static str)]| {
+      move |name: &str| {
+        vars
+          .iter()
+          .find(|(var, _)| *var == name)
+          .map(|(_, value)| value.to_string())
+      }
     };
 
-    Ok(flags)
+    // Variables take precedence over the config file.
+    assert_eq!(
+      Gateway::from_vars(
+        &config,
+        vars(&[("THREE_EM_PORT", "1985"), ("THREE_EM_PROTOCOL", "http")])
+      )
+      .unwrap(),
+      Gateway {
+        host: String::from("localhost"),
+        port: 1985,
+        protocol: String::from("http"),
+      }
+    );
+    assert_eq!(
+      Gateway::from_vars(&Config::default(), vars(&[])).unwrap(),
+      Gateway {
+        host: String::from("arweave.net"),
+        port: 443,
+        protocol: String::from("https"),
+      }
+    );
+    assert!(
+      Gateway::from_vars(&config, vars(&[("THREE_EM_PROTOCOL", "ftp")]))
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn test_parse_diff() {
+    let cli = Cli::try_parse_from([
+      "three_em",
+      "diff",
+      "--contract-id",
+      "t9T7DIOGxx4VWXoCEeYYarFYeERTpWIC1V3y-BPZgKE",
+      "--from",
+      "749000",
+      "--to",
+      "749180",
+    ])
+    .unwrap();
+
+    match cli.flags {
+      Flags::Diff {
+        from, to, gateway, ..
+      } => {
+        assert_eq!(from, 749000);
+        assert_eq!(to, 749180);
+        // Same gateway defaults as `run`.
+        assert_eq!(
+          gateway.resolve(&Config::default()),
+          Gateway {
+            host: String::from("arweave.net"),
+            port: 443,
+            protocol: String::from("https"),
+          }
+        );
+      }
+      flags => panic!("Unexpected flags: {:?}", flags),
+    }
+
+    // Heights are reported instead of unwrapped.
+    assert!(Cli::try_parse_from([
+      "three_em",
+      "diff",
+      "--contract-id",
+      "id",
+      "--from",
+      "tip",
+      "--to",
+      "2",
+    ])
+    .is_err());
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    // Missing required option
+    assert!(Cli::try_parse_from(["three_em", "run"]).is_err());
+    // Unknown command
+    assert!(Cli::try_parse_from(["three_em", "deploy"]).is_err());
+    // Invalid value
+    assert!(Cli::try_parse_from([
+      "three_em",
+      "diff",
+      "--contract-id",
+      "id",
+      "--from",
+      "1",
+      "--to",
+      "2",
+      "--protocol",
+      "ftp",
+    ])
+    .is_err());
+    assert!(Cli::try_parse_from([
+      "three_em",
+      "dry-run",
+      "--file",
+      "dry.json",
+      "--interactive",
+      "--watch",
+    ])
+    .is_err());
+  }
+
+  #[test]
+  fn test_config_defaults() {
+    let config = Config::parse(
+      r#"
+host = "localhost"
+protocol = "http"
+output = "pretty"
+heap_limit = 512
+"#,
+    )
+    .unwrap();
+    let cli = Cli::try_parse_from([
+      "three_em",
+      "dry-run",
+      "--file",
+      "dry.json",
+      "--host",
+      "gateway.local",
+    ])
+    .unwrap();
+
+    match cli.flags {
+      Flags::DryRun {
+        gateway,
+        output,
+        limits,
+        ..
+      } => {
+        // Flags take precedence over the config file.
+        assert_eq!(
+          gateway.resolve(&config),
+          Gateway {
+            host: String::from("gateway.local"),
+            port: 443,
+            protocol: String::from("http"),
+          }
+        );
+        assert_eq!(output.resolve(&config), OutputFormat::Pretty);
+        assert_eq!(limits.resolve(&config), Some(512));
+      }
+      flags => panic!("Unexpected flags: {:?}", flags),
+    }
+  }
+
+  #[test]
+  fn test_parse_cache() {
+    let cli =
+      Cli::try_parse_from(["three_em", "cache", "prune", "--older-than", "2h"])
+        .unwrap();
+    match cli.flags {
+      Flags::Cache {
+        command: CacheCommand::Prune { older_than },
+      } => assert_eq!(older_than, Duration::from_secs(7200)),
+      flags => panic!("Unexpected flags: {:?}", flags),
+    }
+
+    assert_eq!(parse_duration("30d"), Ok(Duration::from_secs(2592000)));
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("12").is_err());
+    assert!(parse_duration("ad").is_err());
+    assert!(parse_duration("1ä").is_err());
   }
 }
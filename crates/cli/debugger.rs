@@ -0,0 +1,350 @@
+use crate::dry_run::{build_interaction, DryRun, RawInteractions};
+use crate::json_path;
+use deno_core::error::AnyError;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
+use three_em_arweave::arweave::{Arweave, ArweaveProtocol};
+use three_em_arweave::miscellaneous::ContractType;
+use three_em_executor::executor::{
+  evolve_runtime, generate_interaction_context, rollback_runtime,
+};
+use three_em_executor::runtime::{CallOutcome, ContractRuntime};
+
+const HELP: &str = "\
+Commands:
+  next, n, <enter>       Evaluate the next interaction
+  state, s [path]        Print the state, or the values matched by a JSON path
+  inject <caller> <json> Evaluate an interaction that is not in the file
+  continue, c            Evaluate the remaining interactions
+  help, h                Print this message
+  quit, q                Stop debugging";
+
+/// Steps through the interactions of a dry run file on a single runtime,
+/// the way `raw_execute_contract` evaluates them.
+pub struct Debugger<'a> {
+  arweave: &'a Arweave,
+  arweave_info: (i32, String, String),
+  contract_type: ContractType,
+  contract_src: Vec<u8>,
+  source_tx: String,
+  settings: HashMap<String, Value>,
+  rt: ContractRuntime,
+  pending: VecDeque<RawInteractions>,
+  total: usize,
+  steps: usize,
+  injected: usize,
+}
+
+impl<'a> Debugger<'a> {
+  pub async fn new(
+    dry: DryRun,
+    contract_src: Vec<u8>,
+    arweave: &'a Arweave,
+    mut settings: HashMap<String, Value>,
+  ) -> Result<Debugger<'a>, AnyError> {
+    if let Some(heap_limit) = arweave.heap_limit {
+      settings
+        .entry(String::from("HEAP_LIMIT"))
+        .or_insert_with(|| Value::String(heap_limit.to_string()));
+    }
+    let arweave_info = (
+      arweave.port,
+      arweave.host.to_owned(),
+      match arweave.protocol {
+        ArweaveProtocol::HTTPS => String::from("https"),
+        ArweaveProtocol::HTTP => String::from("http"),
+      },
+    );
+    let rt = ContractRuntime::new(
+      "",
+      &dry.contract_type,
+      &contract_src,
+      dry.initial_state,
+      arweave_info.to_owned(),
+      settings.clone(),
+      None,
+    )
+    .await?;
+
+    Ok(Debugger {
+      arweave,
+      arweave_info,
+      contract_type: dry.contract_type,
+      contract_src,
+      source_tx: String::new(),
+      settings,
+      rt,
+      total: dry.interactions.len(),
+      pending: dry.interactions.into(),
+      steps: 0,
+      injected: 0,
+    })
+  }
+
+  pub fn state(&mut self) -> Result<Value, AnyError> {
+    self.rt.get_state()
+  }
+
+  // Same recovery as a full evaluation: the failed interaction leaves no
+  // trace in the state, the KV store or the contract's globals.
+  async fn rollback(&mut self) -> Result<(), AnyError> {
+    self.rt = rollback_runtime(
+      &mut self.rt,
+      "",
+      &self.contract_type,
+      &self.contract_src,
+      &self.arweave_info,
+      &self.settings,
+      None,
+    )
+    .await?;
+    Ok(())
+  }
+
+  /// Evaluates an interaction and describes what it did.
+  async fn step<W: Write>(
+    &mut self,
+    interaction: RawInteractions,
+    output: &mut W,
+  ) -> Result<(), AnyError> {
+    let tx = build_interaction(&interaction).node;
+    let call_input = serde_json::json!({
+      "input": interaction.input,
+      "caller": interaction.caller
+    });
+
+    writeln!(output, "id:     {}", interaction.id)?;
+    writeln!(output, "caller: {}", interaction.caller)?;
+    writeln!(output, "input:  {}", interaction.input)?;
+
+    self.rt.begin_interaction(HashMap::new())?;
+    let call_result = self
+      .rt
+      .call(call_input, generate_interaction_context(&tx))
+      .await;
+    self.rt.end_interaction();
+
+    match call_result {
+      Ok(CallOutcome::Applied {
+        result,
+        state_updated,
+      }) => {
+        if let Some(result) = result {
+          writeln!(output, "result: {}", result)?;
+        }
+        writeln!(output, "state:  {}", changed(state_updated))?;
+      }
+      Ok(CallOutcome::Evolve {
+        source: evolve,
+        result,
+      }) => {
+        if evolve != self.source_tx {
+          match evolve_runtime(
+            &mut self.rt,
+            "",
+            &evolve,
+            self.arweave,
+            &self.arweave_info,
+            &self.settings,
+            None,
+          )
+          .await
+          {
+            Ok((evolved, source)) => {
+              self.rt = evolved;
+              self.contract_type = source.contract_type;
+              self.contract_src = source.contract_src;
+              self.source_tx = evolve.to_owned();
+            }
+            Err(err) => {
+              self.rollback().await?;
+              writeln!(output, "error:  {}", err)?;
+              writeln!(output, "state:  {}", changed(false))?;
+              return Ok(());
+            }
+          }
+        }
+        if let Some(result) = result {
+          writeln!(output, "result: {}", result)?;
+        }
+        writeln!(output, "evolve: {}", evolve)?;
+        writeln!(output, "state:  {}", changed(true))?;
+      }
+      Err(err) => {
+        self.rollback().await?;
+
+        writeln!(output, "error:  {}", err)?;
+        writeln!(output, "state:  {}", changed(false))?;
+      }
+    }
+
+    Ok(())
+  }
+
+  async fn next<W: Write>(&mut self, output: &mut W) -> Result<bool, AnyError> {
+    match self.pending.pop_front() {
+      Some(interaction) => {
+        self.steps += 1;
+        writeln!(output, "[{}/{}]", self.steps, self.total)?;
+        self.step(interaction, output).await?;
+        Ok(true)
+      }
+      None => {
+        writeln!(output, "No interactions left")?;
+        Ok(false)
+      }
+    }
+  }
+
+  async fn inject<W: Write>(
+    &mut self,
+    args: &str,
+    output: &mut W,
+  ) -> Result<(), AnyError> {
+    let (caller, input) = args
+      .split_once(char::is_whitespace)
+      .ok_or_else(|| AnyError::msg("Usage: inject <caller> <json input>"))?;
+    let input: Value = serde_json::from_str(input.trim())
+      .map_err(|err| AnyError::msg(format!("Invalid input: {}", err)))?;
+
+    self.injected += 1;
+    let interaction = RawInteractions {
+      id: format!("injected-{}", self.injected),
+      caller: caller.to_owned(),
+      input,
+      ..Default::default()
+    };
+    writeln!(output, "[injected]")?;
+    self.step(interaction, output).await
+  }
+
+  fn print_state<W: Write>(
+    &mut self,
+    path: &str,
+    output: &mut W,
+  ) -> Result<(), AnyError> {
+    let state = self.rt.get_state()?;
+    if path.is_empty() {
+      writeln!(output, "{}", serde_json::to_string_pretty(&state)?)?;
+      return Ok(());
+    }
+
+    for value in json_path::query(&state, path)? {
+      writeln!(output, "{}", serde_json::to_string_pretty(value)?)?;
+    }
+    Ok(())
+  }
+
+  /// Reads commands from `input` until the user quits or continues past the
+  /// last interaction.
+  pub async fn run<R: BufRead, W: Write>(
+    &mut self,
+    input: R,
+    mut output: W,
+  ) -> Result<(), AnyError> {
+    writeln!(
+      output,
+      "{} interactions loaded. Type `help` for a list of commands.",
+      self.total
+    )?;
+
+    let mut lines = input.lines();
+    loop {
+      write!(output, "> ")?;
+      output.flush()?;
+
+      let line = match lines.next() {
+        Some(line) => line?,
+        None => break,
+      };
+      let line = line.trim();
+      let (command, args) = line
+        .split_once(char::is_whitespace)
+        .map(|(command, args)| (command, args.trim()))
+        .unwrap_or((line, ""));
+
+      let result = match command {
+        "" | "n" | "next" => self.next(&mut output).await.map(|_| ()),
+        "s" | "state" => self.print_state(args, &mut output),
+        "inject" => self.inject(args, &mut output).await,
+        "c" | "continue" => {
+          while self.next(&mut output).await? {}
+          break;
+        }
+        "h" | "help" => writeln!(output, "{}", HELP).map_err(AnyError::from),
+        "q" | "quit" => break,
+        _ => Err(AnyError::msg(format!(
+          "Unknown command `{}`. Type `help` for a list of commands.",
+          command
+        ))),
+      };
+
+      // Mistyped commands should not end the session.
+      if let Err(err) = result {
+        writeln!(output, "{}", err)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn changed(state_updated: bool) -> &'static str {
+  if state_updated {
+    "changed"
+  } else {
+    "unchanged"
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::debugger::Debugger;
+  use crate::dry_run::read_dry_run_file;
+  use std::collections::HashMap;
+  use three_em_arweave::arweave::Arweave;
+  use three_em_arweave::cache::ArweaveCache;
+  use three_em_arweave::cache::CacheExt;
+
+  #[tokio::test]
+  async fn test_debugger() {
+    let file =
+      read_dry_run_file("../../testdata/contracts/dry_run_users_contract.json")
+        .unwrap();
+    let contract_src = file.read_contract_source().unwrap();
+    let arweave = Arweave::new(
+      443,
+      String::from("arweave.net"),
+      String::from("https"),
+      ArweaveCache::new(),
+    );
+
+    let mut debugger =
+      Debugger::new(file.run, contract_src, &arweave, HashMap::new())
+        .await
+        .unwrap();
+    let commands = "next\nstate $.users[0]\nfoo\n\
+      inject injected-caller {\"function\":\"add\",\"name\":\"Tate\"}\n\
+      continue\n";
+    let mut output = Vec::new();
+    debugger
+      .run(commands.as_bytes(), &mut output)
+      .await
+      .unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.contains("[1/4]"));
+    assert!(output.contains("\"Andres Pirela\""));
+    assert!(output.contains("Unknown command `foo`"));
+    assert!(output.contains("[injected]"));
+    assert!(output.contains("error:  Error: Invalid operation"));
+    assert!(output.contains("No interactions left"));
+    assert_eq!(
+      debugger.state().unwrap(),
+      serde_json::json!({
+        "users": ["Andres Pirela", "Tate", "Divy", "Some Other"]
+      })
+    );
+  }
+}
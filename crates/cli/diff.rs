@@ -0,0 +1,32 @@
+use crate::cli::parse::OutputFormat;
+use crate::output::write_value;
+use deno_core::error::AnyError;
+use three_em_arweave::arweave::Arweave;
+use three_em_arweave::cache::ArweaveCache;
+use three_em_arweave::cache::CacheExt;
+use three_em_executor::diff::diff_contract;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn diff(
+  port: i32,
+  host: String,
+  protocol: String,
+  tx: String,
+  from: usize,
+  to: usize,
+  output: OutputFormat,
+  no_cache: bool,
+  show_errors: bool,
+  heap_limit: Option<usize>,
+) -> Result<(), AnyError> {
+  let arweave = Arweave::new(port, host, protocol, ArweaveCache::new())
+    .with_heap_limit(heap_limit);
+  let state_diff =
+    diff_contract(tx, from, to, !no_cache, show_errors, &arweave).await?;
+
+  write_value(
+    std::io::stdout().lock(),
+    &serde_json::to_value(state_diff)?,
+    output,
+  )
+}
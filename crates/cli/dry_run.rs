@@ -1,195 +1,416 @@
+use crate::cli::parse::OutputFormat;
+use crate::debugger::Debugger;
+use crate::evm_dry_run::evm_dry_run;
+use crate::expectations::{
+  check_expectations, needs_trace, trace_states, InteractionExpectation,
+  Mismatch, StateExpectation,
+};
+use crate::output::write_value;
 use deno_core::error::AnyError;
-use indexmap::map::IndexMap;
-use serde::Deserialize;
 use serde::Serialize;
+use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::error::Error;
 use std::path::Path;
-use three_em_arweave::arweave::Arweave;
+use three_em_arweave::arweave::{Arweave, LoadedContract};
 use three_em_arweave::cache::ArweaveCache;
 use three_em_arweave::cache::CacheExt;
 use three_em_arweave::gql_result::{
   GQLAmountInterface, GQLEdgeInterface, GQLTagInterface,
 };
 use three_em_arweave::miscellaneous::ContractType;
-use three_em_executor::executor::{raw_execute_contract, ExecuteResult};
+use three_em_executor::executor::{
+  raw_execute_contract, with_validity, ExecuteResult,
+};
 use three_em_executor::test_util::{
   generate_fake_interaction, generate_fake_loaded_contract_data,
 };
+use three_em_executor::trace::write_trace;
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct RawInteractions {
-  id: String,
-  caller: String,
-  input: Value,
+  pub id: String,
+  pub caller: String,
+  pub input: Value,
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
-  block_id: Option<String>,
+  pub block_id: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
-  block_height: Option<usize>,
+  pub block_height: Option<usize>,
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
-  block_timestamp: Option<usize>,
+  pub block_timestamp: Option<usize>,
+  /// Difficulty of the block, used by EVM contracts.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  pub block_difficulty: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
   quantity: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
   reward: Option<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  #[serde(default, deserialize_with = "deserialize_tags")]
+  tags: Vec<GQLTagInterface>,
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
-  tags: Option<GQLTagInterface>,
+  recipient: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
-  recipient: Option<String>,
+  pub expect: Option<InteractionExpectation>,
+}
+
+// Older dry run files hold a single tag instead of a list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Tags {
+  One(GQLTagInterface),
+  Many(Vec<GQLTagInterface>),
+}
+
+fn deserialize_tags<'de, D>(
+  deserializer: D,
+) -> Result<Vec<GQLTagInterface>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  Ok(match Tags::deserialize(deserializer)? {
+    Tags::One(tag) => vec![tag],
+    Tags::Many(tags) => tags,
+  })
+}
+
+/// Contract and interactions evaluated by a dry run.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRun {
+  pub contract_type: ContractType,
+  pub initial_state: Value,
+  pub interactions: Vec<RawInteractions>,
+  /// Expected final state.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  pub expect: Option<StateExpectation>,
+  /// Responses served to the contract by offline dry runs.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  pub fixtures: Option<Value>,
 }
 
+/// Dry run read from a file, which points to the source of its contract.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DryRunFile {
-  contract_type: ContractType,
-  contract_source: String,
-  initial_state: Value,
-  interactions: Vec<RawInteractions>,
+  /// Path of the contract source.
+  pub contract_source: String,
+  #[serde(flatten)]
+  pub run: DryRun,
 }
 
+impl DryRunFile {
+  pub fn read_contract_source(&self) -> Result<Vec<u8>, AnyError> {
+    std::fs::read(&self.contract_source).map_err(|err| {
+      AnyError::msg(format!(
+        "Contract source {} could not be read: {}",
+        self.contract_source, err
+      ))
+    })
+  }
+}
+
+/// Executor settings of a dry run. Offline dry runs serve the fixtures of
+/// the file and fail on any other network access.
+pub fn dry_run_settings(dry: &DryRun, offline: bool) -> HashMap<String, Value> {
+  let mut settings = HashMap::new();
+  if offline {
+    settings.insert(
+      String::from("FIXTURES"),
+      dry
+        .fixtures
+        .to_owned()
+        .unwrap_or_else(|| serde_json::json!({})),
+    );
+  }
+  settings
+}
+
+/// Contract of a dry run, given its source.
+pub fn dry_run_contract(dry: &DryRun, contract_src: &[u8]) -> LoadedContract {
+  generate_fake_loaded_contract_data(
+    contract_src,
+    dry.contract_type.to_owned(),
+    dry.initial_state.to_string(),
+  )
+}
+
+/// Interaction built from an entry of a dry run file.
+pub fn build_interaction(data: &RawInteractions) -> GQLEdgeInterface {
+  let mut interaction = generate_fake_interaction(
+    data.input.to_owned(),
+    &(data.id.to_owned())[..],
+    data.block_id.to_owned(),
+    data.block_height.to_owned(),
+    Some(data.caller.to_owned()),
+    data.recipient.to_owned(),
+    None,
+    Some(GQLAmountInterface {
+      winston: {
+        let quantity = data.quantity.to_owned();
+        if quantity.is_some() {
+          Some(quantity.unwrap())
+        } else {
+          Some(String::from("0"))
+        }
+      },
+      ar: None,
+    }),
+    Some(GQLAmountInterface {
+      winston: {
+        let reward = data.reward.to_owned();
+        if reward.is_some() {
+          Some(reward.unwrap())
+        } else {
+          Some(String::from("0"))
+        }
+      },
+      ar: None,
+    }),
+    data.block_timestamp,
+  );
+  interaction.node.tags.extend(data.tags.iter().cloned());
+  interaction
+}
+
+/// Evaluates the interactions of a dry run. Interactions are traced when
+/// `trace` is set or the dry run expects states between interactions.
 #[allow(clippy::too_many_arguments)]
-pub async fn dry_run_result(
+pub async fn execute_dry_run(
   port: i32,
   host: String,
   protocol: String,
-  file: String,
-) -> ExecuteResult {
-  let dry = read_dry_run_file(file);
-
-  let file =
-    std::fs::read(dry.contract_source).expect("File source does not exist");
-  let dry_contract = generate_fake_loaded_contract_data(
-    file.as_slice(),
-    dry.contract_type,
-    dry.initial_state.to_string(),
-  );
-
+  dry: &DryRun,
+  contract_src: &[u8],
+  trace: bool,
+  offline: bool,
+  heap_limit: Option<usize>,
+) -> Result<ExecuteResult, AnyError> {
+  let dry_contract = dry_run_contract(dry, contract_src);
   let interactions = dry
     .interactions
     .iter()
-    .map(|data| {
-      generate_fake_interaction(
-        data.input.to_owned(),
-        &(data.id.to_owned())[..],
-        data.block_id.to_owned(),
-        data.block_height.to_owned(),
-        Some(data.caller.to_owned()),
-        data.recipient.to_owned(),
-        None,
-        Some(GQLAmountInterface {
-          winston: {
-            let quantity = data.quantity.to_owned();
-            if quantity.is_some() {
-              Some(quantity.unwrap())
-            } else {
-              Some(String::from("0"))
-            }
-          },
-          ar: None,
-        }),
-        Some(GQLAmountInterface {
-          winston: {
-            let reward = data.reward.to_owned();
-            if reward.is_some() {
-              Some(reward.unwrap())
-            } else {
-              Some(String::from("0"))
-            }
-          },
-          ar: None,
-        }),
-        data.block_timestamp,
-      )
-    })
+    .map(build_interaction)
     .collect::<Vec<GQLEdgeInterface>>();
 
   let execution = raw_execute_contract(
     String::from(""),
     dry_contract,
     interactions,
-    IndexMap::new(),
     None,
     true,
     true,
-    |_, _, _| panic!("Unimplemented"),
-    &Arweave::new(port, host, protocol, ArweaveCache::new()),
-    HashMap::new(),
+    |_, _| panic!("Unimplemented"),
+    &Arweave::new(port, host, protocol, ArweaveCache::new())
+      .with_tracing(trace || needs_trace(dry))
+      .with_heap_limit(heap_limit),
+    dry_run_settings(dry, offline),
     None,
   )
-  .await;
-  execution
+  .await?;
+  Ok(execution)
 }
 
+/// Output of a dry run, along with the expectations of the file it did not
+/// meet.
 #[allow(clippy::too_many_arguments)]
-pub async fn dry_run(
+pub async fn evaluate_dry_run(
   port: i32,
   host: String,
   protocol: String,
-  pretty_print: bool,
+  dry: &DryRun,
+  contract_src: &[u8],
   show_validity: bool,
-  file: String,
-) -> Result<(), AnyError> {
-  let execution = dry_run_result(port, host, protocol, file).await;
+  trace: Option<String>,
+  offline: bool,
+  heap_limit: Option<usize>,
+) -> Result<(Value, Vec<Mismatch>), AnyError> {
+  if let ContractType::EVM = dry.contract_type {
+    if trace.is_some() {
+      return Err(AnyError::msg(
+        "Traces are only recorded for JavaScript and WASM contracts",
+      ));
+    }
 
-  if let ExecuteResult::V8(data) = execution {
-    let (state, validity, result) = (
-      data.state,
-      data.validity,
-      data.result.unwrap_or(Value::default()),
-    );
+    let evm = evm_dry_run(dry, contract_src)?;
+    let mismatches =
+      check_expectations(dry, &evm.records, &evm.states, &evm.storage);
+    let value = serde_json::json!({
+      "storage": evm.storage,
+      "result": evm.result,
+    });
     let value = if show_validity {
+      with_validity(value, &evm.validity, &evm.records)
+    } else {
+      value
+    };
+
+    return Ok((value, mismatches));
+  }
+
+  let execution = execute_dry_run(
+    port,
+    host,
+    protocol,
+    dry,
+    contract_src,
+    trace.is_some(),
+    offline,
+    heap_limit,
+  )
+  .await?;
+  let data = match execution {
+    ExecuteResult::V8(data) => data,
+    ExecuteResult::Evm(..) => unreachable!("EVM files are run locally"),
+  };
+
+  if let Some(path) = trace {
+    write_trace(std::fs::File::create(path)?, &data.trace)?;
+  }
+
+  let mismatches = check_expectations(
+    dry,
+    &data.records,
+    &trace_states(&dry.initial_state, &data.trace)?,
+    &data.state,
+  );
+  let (state, result) = (data.state, data.result.unwrap_or(Value::default()));
+  let value = if show_validity {
+    with_validity(
       serde_json::json!({
           "state": state,
-          "validity": validity,
           "result": result
-      })
-    } else {
-      state
-    };
+      }),
+      &data.validity,
+      &data.records,
+    )
+  } else {
+    state
+  };
 
-    if pretty_print {
-      println!("{}", serde_json::to_string_pretty(&value).unwrap());
-    } else {
-      println!("{}", value);
+  Ok((value, mismatches))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn dry_run(
+  port: i32,
+  host: String,
+  protocol: String,
+  output: OutputFormat,
+  show_validity: bool,
+  file: String,
+  trace: Option<String>,
+  offline: bool,
+  heap_limit: Option<usize>,
+) -> Result<(), AnyError> {
+  let file = read_dry_run_file(file)?;
+  let (value, mismatches) = evaluate_dry_run(
+    port,
+    host,
+    protocol,
+    &file.run,
+    &file.read_contract_source()?,
+    show_validity,
+    trace,
+    offline,
+    heap_limit,
+  )
+  .await?;
+
+  write_value(std::io::stdout().lock(), &value, output)?;
+
+  if !mismatches.is_empty() {
+    for mismatch in &mismatches {
+      eprintln!("{}", mismatch);
     }
-  } else {
-    panic!("Dry run is only implemented for WASM and JS contracts");
+    return Err(AnyError::msg(format!(
+      "{} expectation(s) of the dry run were not met",
+      mismatches.len()
+    )));
   }
 
   Ok(())
 }
 
-fn read_dry_run_file<P: AsRef<Path>>(path: P) -> DryRunFile {
-  let data = std::fs::read_to_string(path).expect("Unable to read input file");
-  let res: DryRunFile =
-    serde_json::from_str(&data).expect("Unable to parse input file");
-  res
+/// Steps through the interactions of a dry run file, reading commands from
+/// stdin.
+pub async fn dry_run_interactive(
+  port: i32,
+  host: String,
+  protocol: String,
+  file: String,
+  offline: bool,
+  heap_limit: Option<usize>,
+) -> Result<(), AnyError> {
+  let file = read_dry_run_file(file)?;
+  if let ContractType::EVM = file.run.contract_type {
+    return Err(AnyError::msg(
+      "Interactive dry runs are only available for JavaScript and WASM contracts",
+    ));
+  }
+  let contract_src = file.read_contract_source()?;
+  let arweave = Arweave::new(port, host, protocol, ArweaveCache::new())
+    .with_heap_limit(heap_limit);
+  let settings = dry_run_settings(&file.run, offline);
+
+  let mut debugger =
+    Debugger::new(file.run, contract_src, &arweave, settings).await?;
+  debugger
+    .run(std::io::stdin().lock(), std::io::stdout())
+    .await
+}
+
+pub fn read_dry_run_file<P: AsRef<Path>>(
+  path: P,
+) -> Result<DryRunFile, AnyError> {
+  let data = std::fs::read_to_string(path).map_err(|err| {
+    AnyError::msg(format!("Unable to read input file: {}", err))
+  })?;
+  let res: DryRunFile = serde_json::from_str(&data).map_err(|err| {
+    AnyError::msg(format!("Unable to parse input file: {}", err))
+  })?;
+  Ok(res)
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::dry_run::{dry_run, dry_run_result};
+  use crate::dry_run::{
+    build_interaction, execute_dry_run, read_dry_run_file, RawInteractions,
+  };
+  use crate::expectations::{check_expectations, trace_states};
   use three_em_executor::executor::ExecuteResult;
 
   #[tokio::test]
   async fn test_dry_run() {
-    let execution = dry_run_result(
+    let file = read_dry_run_file(
+      // Exit cargo directory
+      "../../testdata/contracts/dry_run_users_contract.json",
+    )
+    .unwrap();
+    let execution = execute_dry_run(
       443,
       String::from("arweave.net"),
       String::from("https"),
-      // Exit cargo directory
-      String::from("../../testdata/contracts/dry_run_users_contract.json"),
+      &file.run,
+      &file.read_contract_source().unwrap(),
+      false,
+      false,
+      None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(data) = execution {
       let value = data.state;
@@ -208,4 +429,77 @@ mod tests {
       panic!("Unexpected result");
     }
   }
+  #[tokio::test]
+  async fn test_dry_run_offline() {
+    let file =
+      read_dry_run_file("../../testdata/contracts/dry_run_offline.json")
+        .unwrap();
+    let dry = &file.run;
+    // Unreachable gateway: every response must come from the fixtures.
+    let execution = execute_dry_run(
+      1,
+      String::from("localhost"),
+      String::from("http"),
+      dry,
+      &file.read_contract_source().unwrap(),
+      false,
+      true,
+      None,
+    )
+    .await
+    .unwrap();
+
+    if let ExecuteResult::V8(data) = execution {
+      assert!(check_expectations(
+        dry,
+        &data.records,
+        &trace_states(&dry.initial_state, &data.trace).unwrap(),
+        &data.state,
+      )
+      .is_empty());
+      let error = data.records["tx4"].error.as_ref().unwrap();
+      assert!(error
+        .message
+        .contains("No fixture for the balance of wallet unknown-address"));
+    } else {
+      panic!("Unexpected result");
+    }
+  }
+
+  #[test]
+  fn test_dry_run_tags() {
+    let interactions: Vec<RawInteractions> =
+      serde_json::from_value(serde_json::json!([
+        {
+          "id": "tx1",
+          "caller": "ap-address",
+          "input": {},
+          "tags": { "name": "App-Name", "value": "users" }
+        },
+        {
+          "id": "tx2",
+          "caller": "ap-address",
+          "input": {},
+          "tags": [
+            { "name": "App-Name", "value": "users" },
+            { "name": "App-Version", "value": "1" }
+          ]
+        }
+      ]))
+      .unwrap();
+
+    let tags = |interaction: &RawInteractions| {
+      build_interaction(interaction)
+        .node
+        .tags
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect::<Vec<String>>()
+    };
+    assert_eq!(tags(&interactions[0]), vec!["Input", "App-Name"]);
+    assert_eq!(
+      tags(&interactions[1]),
+      vec!["Input", "App-Name", "App-Version"]
+    );
+  }
 }
@@ -0,0 +1,234 @@
+use crate::dry_run::{DryRun, RawInteractions};
+use deno_core::error::AnyError;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use three_em_arweave::miscellaneous::ValidityRecord;
+use three_em_evm::{BlockInfo, Machine, Storage, U256};
+use three_em_executor::executor::execute_evm_call;
+use three_em_executor::{nop_cost_fn, ValidityRecords, ValidityTable};
+
+/// Outcome of an EVM dry run. Storage is decoded into a map of hex slots
+/// to hex values of the contract account.
+pub struct EvmDryRun {
+  pub storage: Value,
+  /// Return data of the last successful call, hex encoded.
+  pub result: String,
+  pub validity: ValidityTable,
+  pub records: ValidityRecords,
+  /// Storage right after each interaction.
+  pub states: HashMap<String, Value>,
+}
+
+// Accepts `0x` prefixed hex or decimal words.
+fn parse_word(word: &str) -> Result<U256, AnyError> {
+  let invalid_word = || AnyError::msg(format!("Invalid EVM word: {}", word));
+  match word.strip_prefix("0x") {
+    Some(hex) => U256::from_str_radix(hex, 16).map_err(|_| invalid_word()),
+    None => U256::from_dec_str(word).map_err(|_| invalid_word()),
+  }
+}
+
+/// Runtime bytecode held by a contract source, either as hex or as a
+/// Solidity artifact.
+pub fn parse_bytecode(contract_src: &[u8]) -> Result<Vec<u8>, AnyError> {
+  let source = std::str::from_utf8(contract_src).map_err(|err| {
+    AnyError::msg(format!("Contract source is not valid UTF-8: {}", err))
+  })?;
+
+  // Hardhat and Truffle artifacts hold the runtime bytecode as a string,
+  // Foundry artifacts as an object and solc's standard JSON under `evm`.
+  let code = match serde_json::from_str::<Value>(source) {
+    Ok(artifact) if artifact.is_object() => [
+      "/deployedBytecode",
+      "/deployedBytecode/object",
+      "/evm/deployedBytecode/object",
+    ]
+    .iter()
+    .find_map(|pointer| artifact.pointer(pointer).and_then(Value::as_str))
+    .map(String::from)
+    .ok_or_else(|| {
+      AnyError::msg("No deployed bytecode found in the contract artifact")
+    })?,
+    _ => source.trim().to_owned(),
+  };
+
+  hex::decode(code.trim_start_matches("0x"))
+    .map_err(|err| AnyError::msg(format!("Invalid bytecode: {}", err)))
+}
+
+/// Storage of the contract account from a map of slots to values.
+pub fn parse_storage(state: &Value) -> Result<Storage, AnyError> {
+  let account = U256::zero();
+  let mut storage = Storage::new(account);
+
+  let slots = match state {
+    Value::Object(slots) => slots,
+    Value::Null => return Ok(storage),
+    _ => {
+      return Err(AnyError::msg(
+        "EVM initial state must map storage slots to values",
+      ))
+    }
+  };
+
+  for (slot, value) in slots {
+    let value = match value {
+      Value::String(value) => parse_word(value)?,
+      Value::Number(value) => parse_word(&value.to_string())?,
+      _ => return Err(AnyError::msg(format!("Invalid EVM word: {}", value))),
+    };
+    storage.insert(&account, parse_word(slot)?, value);
+  }
+
+  Ok(storage)
+}
+
+pub fn decode_storage(storage: &Storage) -> Value {
+  let slots: BTreeMap<&U256, &U256> = storage
+    .inner
+    .get(&U256::zero())
+    .map(|slots| slots.iter().collect())
+    .unwrap_or_default();
+
+  Value::Object(
+    slots
+      .into_iter()
+      .map(|(slot, value)| {
+        (
+          format!("{:#x}", slot),
+          Value::String(format!("{:#x}", value)),
+        )
+      })
+      .collect(),
+  )
+}
+
+fn block_info(data: &RawInteractions) -> Result<BlockInfo, AnyError> {
+  let word = |word: &Option<String>| {
+    word
+      .as_deref()
+      .map(parse_word)
+      .transpose()
+      .map(Option::unwrap_or_default)
+  };
+
+  Ok(BlockInfo {
+    timestamp: U256::from(data.block_timestamp.unwrap_or_default()),
+    difficulty: word(&data.block_difficulty)?,
+    block_hash: word(&data.block_id)?,
+    number: U256::from(data.block_height.unwrap_or_default()),
+  })
+}
+
+fn call_data(input: &Value) -> Result<Vec<u8>, ValidityRecord> {
+  let invalid_call_data = |reason: String| {
+    ValidityRecord::invalid(
+      "invalid_call_data",
+      format!("Invalid call data: {}", reason),
+    )
+  };
+
+  let input = input
+    .as_str()
+    .ok_or_else(|| invalid_call_data(String::from("expected a hex string")))?;
+  hex::decode(input.trim_start_matches("0x"))
+    .map_err(|err| invalid_call_data(err.to_string()))
+}
+
+/// Evaluates the calls of an EVM dry run. Contracts are run on their own,
+/// so calls to other contracts fail instead of reaching a gateway.
+pub fn evm_dry_run(
+  dry: &DryRun,
+  contract_src: &[u8],
+) -> Result<EvmDryRun, AnyError> {
+  let bytecode = parse_bytecode(contract_src)?;
+  let mut storage = parse_storage(&dry.initial_state)?;
+  let mut result = Vec::new();
+  let mut validity = ValidityTable::new();
+  let mut records = ValidityRecords::new();
+  let mut states = HashMap::new();
+
+  for data in &dry.interactions {
+    let started = Instant::now();
+    let block_info = block_info(data)?;
+
+    let record = match call_data(&data.input) {
+      Ok(call_data) => execute_evm_call(
+        Machine::new_with_data(nop_cost_fn, call_data),
+        &bytecode,
+        block_info,
+        &mut storage,
+        &mut result,
+      ),
+      Err(record) => record,
+    };
+    let record = ValidityRecord {
+      wall_time_us: started.elapsed().as_micros() as u64,
+      ..record
+    };
+
+    validity.insert(data.id.to_owned(), record.to_legacy(true));
+    records.insert(data.id.to_owned(), record);
+    states.insert(data.id.to_owned(), decode_storage(&storage));
+  }
+
+  Ok(EvmDryRun {
+    storage: decode_storage(&storage),
+    result: hex::encode(result),
+    validity,
+    records,
+    states,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::dry_run::read_dry_run_file;
+  use crate::evm_dry_run::{
+    decode_storage, evm_dry_run, parse_bytecode, parse_storage,
+  };
+  use crate::expectations::check_expectations;
+
+  #[test]
+  fn test_evm_dry_run() {
+    let file =
+      read_dry_run_file("../../testdata/evm/dry_run_storage.json").unwrap();
+    let evm =
+      evm_dry_run(&file.run, &file.read_contract_source().unwrap()).unwrap();
+
+    assert_eq!(evm.storage, serde_json::json!({ "0x0": "0x2" }));
+    assert_eq!(
+      evm.result,
+      "0000000000000000000000000000000000000000000000000000000000000002"
+    );
+    assert_eq!(evm.validity.get("store").unwrap(), true);
+    let error = evm.records["bad-call-data"].error.as_ref().unwrap();
+    assert_eq!(error.kind, "invalid_call_data");
+    assert!(check_expectations(
+      &file.run,
+      &evm.records,
+      &evm.states,
+      &evm.storage
+    )
+    .is_empty());
+  }
+
+  #[test]
+  fn test_load_evm_contract() {
+    let bytecode = |path: &str| parse_bytecode(&std::fs::read(path).unwrap());
+    assert_eq!(
+      bytecode("../../testdata/evm/storage.json").unwrap(),
+      bytecode("../../testdata/evm/storage.hex").unwrap()
+    );
+    assert!(parse_bytecode(b"{}").is_err());
+
+    let storage = parse_storage(&serde_json::json!({ "0x1": "10" })).unwrap();
+    assert_eq!(
+      decode_storage(&storage),
+      serde_json::json!({ "0x1": "0xa" })
+    );
+    assert!(parse_storage(&serde_json::json!({ "0x1": "0xzz" })).is_err());
+    assert!(parse_storage(&serde_json::json!([])).is_err());
+  }
+}
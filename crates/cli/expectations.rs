@@ -0,0 +1,260 @@
+use crate::dry_run::DryRun;
+use deno_core::error::AnyError;
+use indexmap::map::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use three_em_executor::json_patch::apply;
+use three_em_executor::trace::TraceEntry;
+use three_em_executor::ValidityRecords;
+
+/// Expected state of a contract, in full or at RFC 6901 JSON pointers.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StateExpectation {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  pub state: Option<Value>,
+  #[serde(skip_serializing_if = "IndexMap::is_empty")]
+  #[serde(default)]
+  pub state_pointers: IndexMap<String, Value>,
+}
+
+impl StateExpectation {
+  pub fn is_empty(&self) -> bool {
+    self.state.is_none() && self.state_pointers.is_empty()
+  }
+
+  fn check(
+    &self,
+    interaction: Option<&str>,
+    state: &Value,
+    mismatches: &mut Vec<Mismatch>,
+  ) {
+    if let Some(expected) = &self.state {
+      if expected != state {
+        mismatches.push(Mismatch {
+          interaction: interaction.map(String::from),
+          subject: String::from("state"),
+          expected: expected.to_owned(),
+          actual: Some(state.to_owned()),
+        });
+      }
+    }
+
+    for (pointer, expected) in &self.state_pointers {
+      let actual = state.pointer(pointer);
+      if actual != Some(expected) {
+        mismatches.push(Mismatch {
+          interaction: interaction.map(String::from),
+          subject: format!("state at {}", pointer),
+          expected: expected.to_owned(),
+          actual: actual.cloned(),
+        });
+      }
+    }
+  }
+}
+
+/// Expected outcome of a dry run interaction.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InteractionExpectation {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  pub valid: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  pub result: Option<Value>,
+  /// State right after the interaction.
+  #[serde(flatten)]
+  pub state: StateExpectation,
+}
+
+/// Expectation that a dry run did not meet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Mismatch {
+  /// Interaction the expectation belongs to, or `None` for the final
+  /// state.
+  pub interaction: Option<String>,
+  pub subject: String,
+  pub expected: Value,
+  pub actual: Option<Value>,
+}
+
+impl std::fmt::Display for Mismatch {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(interaction) = &self.interaction {
+      write!(f, "{}: ", interaction)?;
+    }
+    write!(f, "expected {} to be {}, ", self.subject, self.expected)?;
+    match &self.actual {
+      Some(actual) => write!(f, "found {}", actual),
+      None => write!(f, "found nothing"),
+    }
+  }
+}
+
+/// Whether checking the expectations of a dry run needs the state after
+/// every interaction, which only a trace provides.
+pub fn needs_trace(dry: &DryRun) -> bool {
+  dry.interactions.iter().any(|interaction| {
+    interaction
+      .expect
+      .as_ref()
+      .map_or(false, |expect| !expect.state.is_empty())
+  })
+}
+
+/// State after each traced interaction, replayed from the initial state.
+pub fn trace_states(
+  initial_state: &Value,
+  trace: &[TraceEntry],
+) -> Result<HashMap<String, Value>, AnyError> {
+  let mut states = HashMap::new();
+  let mut state = initial_state.to_owned();
+  for entry in trace {
+    apply(&mut state, &entry.patch)?;
+    states.insert(entry.id.to_owned(), state.to_owned());
+  }
+  Ok(states)
+}
+
+/// Compares the outcome of a dry run with its expectations. `states` holds
+/// the state right after each interaction.
+pub fn check_expectations(
+  dry: &DryRun,
+  records: &ValidityRecords,
+  states: &HashMap<String, Value>,
+  final_state: &Value,
+) -> Vec<Mismatch> {
+  let mut mismatches = Vec::new();
+
+  for interaction in &dry.interactions {
+    let expect = match &interaction.expect {
+      Some(expect) => expect,
+      None => continue,
+    };
+    let id = Some(interaction.id.as_str());
+    let record = records.get(&interaction.id);
+
+    if let Some(valid) = expect.valid {
+      let actual = record.map(|record| record.valid);
+      if actual != Some(valid) {
+        mismatches.push(Mismatch {
+          interaction: id.map(String::from),
+          subject: String::from("valid"),
+          expected: Value::Bool(valid),
+          actual: actual.map(Value::Bool),
+        });
+      }
+    }
+
+    if let Some(expected) = &expect.result {
+      let actual = record.and_then(|record| record.result.as_ref());
+      if actual != Some(expected) {
+        mismatches.push(Mismatch {
+          interaction: id.map(String::from),
+          subject: String::from("result"),
+          expected: expected.to_owned(),
+          actual: actual.cloned(),
+        });
+      }
+    }
+
+    if !expect.state.is_empty() {
+      // Interactions that were never evaluated left no state behind.
+      let state = states.get(&interaction.id).unwrap_or(&Value::Null);
+      expect.state.check(id, state, &mut mismatches);
+    }
+  }
+
+  if let Some(expect) = &dry.expect {
+    expect.check(None, final_state, &mut mismatches);
+  }
+
+  mismatches
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::dry_run::{execute_dry_run, read_dry_run_file, DryRun};
+  use crate::expectations::{check_expectations, trace_states, Mismatch};
+  use three_em_executor::executor::ExecuteResult;
+
+  async fn run(dry: &DryRun, contract_src: &[u8]) -> Vec<Mismatch> {
+    let execution = execute_dry_run(
+      443,
+      String::from("arweave.net"),
+      String::from("https"),
+      dry,
+      contract_src,
+      false,
+      false,
+      None,
+    )
+    .await
+    .unwrap();
+
+    match execution {
+      ExecuteResult::V8(result) => check_expectations(
+        dry,
+        &result.records,
+        &trace_states(&dry.initial_state, &result.trace).unwrap(),
+        &result.state,
+      ),
+      ExecuteResult::Evm(..) => panic!("Unexpected result"),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_check_expectations() {
+    let file = read_dry_run_file(
+      "../../testdata/contracts/dry_run_users_expectations.json",
+    )
+    .unwrap();
+    let contract_src = file.read_contract_source().unwrap();
+    let mut dry = file.run;
+    assert!(run(&dry, &contract_src).await.is_empty());
+
+    let expect = dry.interactions[1].expect.as_mut().unwrap();
+    expect.result = Some(serde_json::json!(3));
+    expect
+      .state
+      .state_pointers
+      .insert(String::from("/users/2"), serde_json::json!("Tate"));
+    dry.expect.as_mut().unwrap().state = Some(serde_json::json!({}));
+
+    let mismatches = run(&dry, &contract_src).await;
+    assert_eq!(
+      mismatches,
+      vec![
+        Mismatch {
+          interaction: Some(String::from("tx2")),
+          subject: String::from("result"),
+          expected: serde_json::json!(3),
+          actual: Some(serde_json::json!(2)),
+        },
+        Mismatch {
+          interaction: Some(String::from("tx2")),
+          subject: String::from("state at /users/2"),
+          expected: serde_json::json!("Tate"),
+          actual: None,
+        },
+        Mismatch {
+          interaction: None,
+          subject: String::from("state"),
+          expected: serde_json::json!({}),
+          actual: Some(serde_json::json!({
+            "users": ["Andres Pirela", "Divy", "Some Other"]
+          })),
+        },
+      ]
+    );
+    assert_eq!(
+      mismatches[1].to_string(),
+      "tx2: expected state at /users/2 to be \"Tate\", found nothing"
+    );
+  }
+}
@@ -0,0 +1,179 @@
+use deno_core::error::AnyError;
+use serde_json::Value;
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+  Key(String),
+  Index(usize),
+  Wildcard,
+}
+
+fn invalid_path(path: &str) -> AnyError {
+  AnyError::msg(format!("Invalid JSON path: {}", path))
+}
+
+// Parses `$.users[0]`, `$['a.b']`, `$.balances.*` and `users[*].name` forms.
+// The leading `$` may be left out.
+fn parse(path: &str) -> Result<Vec<Segment>, AnyError> {
+  let chars: Vec<char> = path.trim().chars().collect();
+  let mut segments = Vec::new();
+  let mut i = 0;
+
+  if chars.first() == Some(&'$') {
+    i += 1;
+  }
+
+  let read_key = |i: &mut usize| {
+    let start = *i;
+    while *i < chars.len() && chars[*i] != '.' && chars[*i] != '[' {
+      *i += 1;
+    }
+    chars[start..*i].iter().collect::<String>()
+  };
+
+  while i < chars.len() {
+    match chars[i] {
+      '.' if chars.get(i + 1) == Some(&'*') => {
+        segments.push(Segment::Wildcard);
+        i += 2;
+      }
+      '.' => {
+        i += 1;
+        let key = read_key(&mut i);
+        if key.is_empty() {
+          return Err(invalid_path(path));
+        }
+        segments.push(Segment::Key(key));
+      }
+      '[' => {
+        let end = chars[i..]
+          .iter()
+          .position(|c| *c == ']')
+          .map(|end| i + end)
+          .ok_or_else(|| invalid_path(path))?;
+        let inner: String = chars[i + 1..end].iter().collect();
+        let quoted = inner.len() >= 2
+          && (inner.starts_with('\'') && inner.ends_with('\'')
+            || inner.starts_with('"') && inner.ends_with('"'));
+
+        segments.push(if inner == "*" {
+          Segment::Wildcard
+        } else if quoted {
+          Segment::Key(inner[1..inner.len() - 1].to_owned())
+        } else {
+          Segment::Index(inner.parse().map_err(|_| invalid_path(path))?)
+        });
+        i = end + 1;
+      }
+      _ if i == 0 => {
+        segments.push(Segment::Key(read_key(&mut i)));
+      }
+      _ => return Err(invalid_path(path)),
+    }
+  }
+
+  Ok(segments)
+}
+
+/// Values of `value` matched by a JSON path.
+pub fn query<'a>(
+  value: &'a Value,
+  path: &str,
+) -> Result<Vec<&'a Value>, AnyError> {
+  let mut matches = vec![value];
+
+  for segment in parse(path)? {
+    matches = matches
+      .into_iter()
+      .flat_map(|value| -> Vec<&Value> {
+        match (&segment, value) {
+          (Segment::Key(key), Value::Object(map)) => {
+            map.get(key).into_iter().collect()
+          }
+          (Segment::Index(index), Value::Array(array)) => {
+            array.get(*index).into_iter().collect()
+          }
+          (Segment::Wildcard, Value::Object(map)) => map.values().collect(),
+          (Segment::Wildcard, Value::Array(array)) => array.iter().collect(),
+          _ => Vec::new(),
+        }
+      })
+      .collect();
+  }
+
+  Ok(matches)
+}
+
+/// Part of `value` picked by a JSON pointer, when `selector` starts with
+/// `/`, or by a JSON path. Paths with a wildcard select the array of their
+/// matches.
+pub fn select(value: &Value, selector: &str) -> Result<Value, AnyError> {
+  let no_match =
+    || AnyError::msg(format!("Nothing matches {} in the output", selector));
+
+  if selector.starts_with('/') {
+    return value.pointer(selector).cloned().ok_or_else(no_match);
+  }
+
+  let matches = query(value, selector)?;
+  if parse(selector)?.contains(&Segment::Wildcard) {
+    return Ok(Value::Array(matches.into_iter().cloned().collect()));
+  }
+  matches
+    .first()
+    .map(|value| (*value).clone())
+    .ok_or_else(no_match)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::json_path::{query, select};
+  use serde_json::json;
+
+  #[test]
+  fn test_query() {
+    let state = json!({
+      "users": [{ "name": "Andres" }, { "name": "Divy" }],
+      "balances": { "a.b": 1 },
+      "name": "Users"
+    });
+
+    assert_eq!(query(&state, "$").unwrap(), vec![&state]);
+    assert_eq!(query(&state, "$.name").unwrap(), vec![&json!("Users")]);
+    assert_eq!(query(&state, "name").unwrap(), vec![&json!("Users")]);
+    assert_eq!(
+      query(&state, "$.users[1].name").unwrap(),
+      vec![&json!("Divy")]
+    );
+    assert_eq!(
+      query(&state, "users[*].name").unwrap(),
+      vec![&json!("Andres"), &json!("Divy")]
+    );
+    assert_eq!(query(&state, "$['balances'].*").unwrap(), vec![&json!(1)]);
+    assert_eq!(
+      query(&state, "$.balances[\"a.b\"]").unwrap(),
+      vec![&json!(1)]
+    );
+    assert!(query(&state, "$.missing.name").unwrap().is_empty());
+    assert!(query(&state, "$.users[x]").is_err());
+    assert!(query(&state, "$.users[0").is_err());
+  }
+
+  #[test]
+  fn test_select() {
+    let state = json!({
+      "users": [{ "name": "Andres" }, { "name": "Divy" }],
+      "balances": {}
+    });
+
+    assert_eq!(select(&state, "/users/1/name").unwrap(), json!("Divy"));
+    assert_eq!(select(&state, "$.users[0].name").unwrap(), json!("Andres"));
+    assert_eq!(
+      select(&state, "users[*].name").unwrap(),
+      json!(["Andres", "Divy"])
+    );
+    assert_eq!(select(&state, "$.balances.*").unwrap(), json!([]));
+    assert!(select(&state, "/users/2").is_err());
+    assert!(select(&state, "$.missing").is_err());
+  }
+}
@@ -1,153 +1,882 @@
+use crate::cli::parse::Gateway;
+use crate::dry_run::{evaluate_dry_run, DryRun};
+use crate::tracker::{spawn_sync, Tracker};
 use deno_core::error::AnyError;
-use hyper::http::response::Parts;
-use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE};
+use hyper::{Body, Request, Response, Server, StatusCode};
 use indoc::indoc;
 use routerify::prelude::RequestExt;
-use routerify::{RequestInfo, Router, RouterService};
+use routerify::{Router, RouterService};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::convert::Infallible;
+use std::future::Future;
 use std::net::{IpAddr, SocketAddr};
-use std::num::ParseIntError;
-use std::str::{FromStr, ParseBoolError};
-use three_em_arweave::arweave::Arweave;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use three_em_arweave::arweave::{Arweave, ManualLoadedContract};
 use three_em_arweave::cache::{ArweaveCache, CacheExt};
-use three_em_executor::execute_contract;
-use three_em_executor::executor::ExecuteResult;
-use url::Url;
+use three_em_arweave::gql_result::{GQLEdgeInterface, GQLTagInterface};
+use three_em_arweave::miscellaneous::ContractType;
+use three_em_executor::executor::{with_validity, ExecuteResult};
+use three_em_executor::utils::create_simulated_transaction;
+use three_em_executor::{execute_contract, simulate_contract, ContractError};
+use tokio::runtime::Handle;
+use tokio::time::Instant;
+
+/// How often event streams send a comment, so idle connections are not
+/// closed by proxies.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
 
 pub struct ServerConfiguration {
   pub port: u16,
   pub host: IpAddr,
+  /// Contracts kept evaluated in the background.
+  pub tracked: Vec<String>,
+  /// Gateway tracked contracts are synced through.
+  pub gateway: Gateway,
+  pub sync_interval: Duration,
 }
 
-pub fn build_error(message: &str) -> Response<Body> {
+/// Response of a handler, or the error response that cut it short.
+type Handled = Result<Response<Body>, Response<Body>>;
+
+pub fn json_response(status: StatusCode, value: &Value) -> Response<Body> {
   Response::builder()
-    .status(400)
-    .body(Body::from(
-      serde_json::json!({
-        "status": 400,
-        "message": message})
-      .to_string(),
-    ))
+    .status(status)
+    .header(CONTENT_TYPE, "application/json")
+    .body(Body::from(value.to_string()))
     .unwrap()
 }
 
-async fn echo(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
-  match (req.method(), req.uri().path()) {
-        (&Method::GET, "/evaluate") => {
-            let params: HashMap<String, String> = req
-                .uri()
-                .query()
-                .map(|v| {
-                    url::form_urlencoded::parse(v.as_bytes())
-                        .into_owned()
-                        .collect()
-                })
-                .unwrap_or_else(HashMap::new);
-
-           let contract_id = params.get("contractId").map(|i| i.to_owned());
-           let height = params.get("height").map(|i| i.to_owned());
-           let gateway_host = params.get("gatewayHost").map(|i| i.to_owned()).unwrap_or(String::from("arweave.net"));
-           let gateway_port = params.get("gatewayPort").map(|i| i.to_owned()).unwrap_or(String::from("443"));
-           let gateway_protocol = params.get("gatewayProtocol").map(|i| i.to_owned()).unwrap_or(String::from("https"));
-           let show_validity = params.get("showValidity").map(|i| i.to_owned()).unwrap_or(String::from("false"));
-           let cache = params.get("cache").map(|i| i.to_owned()).unwrap_or(String::from("false"));
-           let show_errors = params.get("showErrors").map(|i| i.to_owned()).unwrap_or(String::from("false"));
-
-           let height = height.map(|h| h.parse::<usize>().unwrap_or(usize::MAX));
-           let show_validity = show_validity.parse::<bool>().unwrap_or(false);
-           let cache = cache.parse::<bool>().unwrap_or(false);
-           let show_errors = show_errors.parse::<bool>().unwrap_or(false);
-           let port = gateway_port.parse::<i32>().unwrap_or(443);
-           let mut response_result: Option<Response<Body>> = None;
-
-           if contract_id.is_none() {
-              response_result = Some(build_error("contractId was not provided in query parameters. A contract id must be provided."));
-            } else {
-                 let arweave = Arweave::new(port, gateway_host.to_owned(), gateway_protocol.to_owned(), ArweaveCache::new());
-                 let execute_result = execute_contract( contract_id.unwrap().to_owned(), height, cache, show_errors, None, None, &arweave).await;
-                match execute_result {
-                     Ok(result) => {
-                         match result {
-                             ExecuteResult::V8(data) => {
-                                 let val = data.state;
-                                 let validity = data.validity;
-                                 let result = data.result.unwrap_or(serde_json::Value::Null);
-                                 if show_validity {
-                                     response_result = Some(Response::new(Body::from(
-                                         serde_json::json!({
-                                             "state": val,
-                                             "validity": validity,
-                                             "result": result
-                                         }).to_string()
-                                     )));
-                                 } else {
-                                     response_result = Some(Response::new(Body::from(
-                                         serde_json::json!({
-                                             "state": val,
-                                             "result": result
-                                         }).to_string()
-                                     )));
-                                 }
-                             },
-                             ExecuteResult::Evm(_, _, _) => {
-                                 response_result = Some(build_error("EVM evaluation is disabled"));
-                             }
-                         }
-                     },
-                     Err(e) => {
-                         response_result = Some(build_error(e.to_string().as_str()));
-                     }
-                 }
-            }
-
-            Ok(response_result.unwrap())
-        }
-        _ => {
-            Ok(Response::new(Body::from(
-                "Try POSTing data to /echo such as: `curl localhost:3000/echo -XPOST -d 'hello world'`",
-            )))
-        }
+pub fn build_error(status: StatusCode, message: &str) -> Response<Body> {
+  json_response(
+    status,
+    &serde_json::json!({
+      "status": status.as_u16(),
+      "message": message}),
+  )
+}
+
+pub fn build_contract_error(err: &ContractError) -> Response<Body> {
+  let status = match err {
+    ContractError::Network(_) => StatusCode::BAD_GATEWAY,
+    ContractError::NotFound(_) => StatusCode::NOT_FOUND,
+    ContractError::RequestDivergence { .. } => StatusCode::CONFLICT,
+    ContractError::RuntimeInit { .. } | ContractError::Execution { .. } => {
+      StatusCode::INTERNAL_SERVER_ERROR
+    }
+    _ => StatusCode::UNPROCESSABLE_ENTITY,
+  };
+
+  json_response(
+    status,
+    &serde_json::json!({
+      "status": status.as_u16(),
+      "kind": err.kind(),
+      "message": err.to_string()}),
+  )
+}
+
+fn build_any_error(err: &AnyError) -> Response<Body> {
+  match err.downcast_ref::<ContractError>() {
+    Some(err) => build_contract_error(err),
+    None => build_error(StatusCode::BAD_REQUEST, &err.to_string()),
+  }
+}
+
+/// Gateway and evaluation options of a request, read from its query string.
+#[derive(Debug, PartialEq)]
+pub struct ContractQuery {
+  pub height: Option<usize>,
+  pub gateway_host: String,
+  pub gateway_port: i32,
+  pub gateway_protocol: String,
+  pub show_validity: bool,
+  pub cache: bool,
+  pub show_errors: bool,
+  pub verify_signatures: bool,
+  pub verify_data: bool,
+  pub enforce_requirements: bool,
+  pub offline: bool,
+}
+
+fn param<T: FromStr>(
+  params: &HashMap<String, String>,
+  name: &str,
+) -> Result<Option<T>, Response<Body>> {
+  params
+    .get(name)
+    .map(|value| {
+      value.parse::<T>().map_err(|_| {
+        build_error(
+          StatusCode::BAD_REQUEST,
+          &format!("Invalid value for {}: {}", name, value),
+        )
+      })
+    })
+    .transpose()
+}
+
+impl ContractQuery {
+  pub fn parse(query: Option<&str>) -> Result<ContractQuery, Response<Body>> {
+    let params: HashMap<String, String> = query
+      .map(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+          .into_owned()
+          .collect()
+      })
+      .unwrap_or_default();
+    let flag = |name: &str, default: bool| {
+      param(&params, name).map(|value| value.unwrap_or(default))
+    };
+
+    Ok(ContractQuery {
+      height: param(&params, "height")?,
+      gateway_host: param(&params, "gatewayHost")?
+        .unwrap_or_else(|| String::from("arweave.net")),
+      gateway_port: param(&params, "gatewayPort")?.unwrap_or(443),
+      gateway_protocol: param(&params, "gatewayProtocol")?
+        .unwrap_or_else(|| String::from("https")),
+      show_validity: flag("showValidity", false)?,
+      cache: flag("cache", true)?,
+      show_errors: flag("showErrors", false)?,
+      verify_signatures: flag("verifySignatures", false)?,
+      verify_data: flag("verifyData", false)?,
+      enforce_requirements: flag("enforceRequirements", false)?,
+      offline: flag("offline", false)?,
+    })
+  }
+
+  fn from_request(
+    req: &Request<Body>,
+  ) -> Result<ContractQuery, Response<Body>> {
+    ContractQuery::parse(req.uri().query())
+  }
+
+  /// Whether the query asks for the evaluation tracked contracts are synced
+  /// with: the latest height, through the gateway of the tracker and with
+  /// the default options.
+  fn is_tracked(&self, gateway: &Gateway) -> bool {
+    self.height.is_none()
+      && self.gateway_host == gateway.host
+      && self.gateway_port == gateway.port
+      && self.gateway_protocol == gateway.protocol
+      && self.cache
+      && !self.show_errors
+      && !self.verify_signatures
+      && !self.verify_data
+      && !self.enforce_requirements
+  }
+
+  fn arweave(&self) -> Arweave {
+    Arweave::new(
+      self.gateway_port,
+      self.gateway_host.to_owned(),
+      self.gateway_protocol.to_owned(),
+      ArweaveCache::new(),
+    )
+    .with_signature_verification(self.verify_signatures)
+    .with_data_verification(self.verify_data)
+    .with_requirement_enforcement(self.enforce_requirements)
+  }
+}
+
+/// Runs a contract evaluation on a blocking thread, like the node bindings
+/// do. Runtimes are not `Send`, and a thread evaluating one contract at a
+/// time drops its isolates in the order they were created.
+async fn evaluate<F, Fut, T>(evaluation: F) -> Result<T, Response<Body>>
+where
+  F: FnOnce() -> Fut + Send + 'static,
+  Fut: Future<Output = T>,
+  T: Send + 'static,
+{
+  tokio::task::spawn_blocking(move || Handle::current().block_on(evaluation()))
+    .await
+    .map_err(|_| {
+      build_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Contract could not be processed",
+      )
+    })
+}
+
+fn contract_id(req: &Request<Body>) -> String {
+  req.param("id").cloned().unwrap_or_default()
+}
+
+fn tracker(req: &Request<Body>) -> Option<Arc<Tracker>> {
+  req.data::<Arc<Tracker>>().cloned()
+}
+
+async fn execute(
+  contract_id: String,
+  query: &ContractQuery,
+  tracker: Option<Arc<Tracker>>,
+) -> Result<ExecuteResult, Response<Body>> {
+  // Tracked contracts are served from their last sync, unless the query
+  // asks for another evaluation.
+  if let Some(execution) = tracker
+    .filter(|tracker| query.is_tracked(tracker.gateway()))
+    .and_then(|tracker| tracker.execution(&contract_id))
+  {
+    return Ok(execution);
+  }
+
+  let (height, cache, show_errors) =
+    (query.height, query.cache, query.show_errors);
+  let arweave = query.arweave();
+
+  evaluate(move || async move {
+    execute_contract(
+      contract_id,
+      height,
+      cache,
+      show_errors,
+      None,
+      None,
+      &arweave,
+    )
+    .await
+  })
+  .await?
+  .map_err(|err| build_contract_error(&err))
+}
+
+async fn read_json<T: DeserializeOwned>(
+  req: Request<Body>,
+) -> Result<T, Response<Body>> {
+  let body = hyper::body::to_bytes(req.into_body())
+    .await
+    .map_err(|err| build_error(StatusCode::BAD_REQUEST, &err.to_string()))?;
+  serde_json::from_slice(&body).map_err(|err| {
+    build_error(
+      StatusCode::BAD_REQUEST,
+      &format!("Invalid request body: {}", err),
+    )
+  })
+}
+
+async fn health(_req: Request<Body>) -> Handled {
+  Ok(json_response(
+    StatusCode::OK,
+    &serde_json::json!({
+      "status": "ok",
+      "version": env!("CARGO_PKG_VERSION"),
+    }),
+  ))
+}
+
+fn state_value(execution: ExecuteResult, show_validity: bool) -> Value {
+  match execution {
+    ExecuteResult::V8(data) => {
+      let result = data.result.unwrap_or(Value::Null);
+      if show_validity {
+        with_validity(
+          serde_json::json!({
+            "state": data.state,
+            "evolutions": data.evolutions,
+            "result": result,
+          }),
+          &data.validity,
+          &data.records,
+        )
+      } else {
+        serde_json::json!({
+          "state": data.state,
+          "result": result,
+        })
+      }
+    }
+    ExecuteResult::Evm(store, result, validity, records) => {
+      let value = serde_json::json!({
+        "result": hex::encode(result),
+        "store": hex::encode(store.raw()),
+      });
+      if show_validity {
+        with_validity(value, &validity, &records)
+      } else {
+        value
+      }
+    }
+  }
+}
+
+async fn state(
+  contract_id: String,
+  query: ContractQuery,
+  tracker: Option<Arc<Tracker>>,
+) -> Handled {
+  let execution = execute(contract_id, &query, tracker).await?;
+  Ok(json_response(
+    StatusCode::OK,
+    &state_value(execution, query.show_validity),
+  ))
+}
+
+async fn contract_state(req: Request<Body>) -> Handled {
+  let query = ContractQuery::from_request(&req)?;
+  let contract_id = contract_id(&req);
+  let tracker = tracker(&req);
+  state(contract_id, query, tracker).await
+}
+
+async fn contract_validity(req: Request<Body>) -> Handled {
+  let query = ContractQuery::from_request(&req)?;
+  let contract_id = contract_id(&req);
+  let tracker = tracker(&req);
+  let value = match execute(contract_id, &query, tracker).await? {
+    ExecuteResult::V8(data) => {
+      with_validity(serde_json::json!({}), &data.validity, &data.records)
+    }
+    ExecuteResult::Evm(_, _, validity, records) => {
+      with_validity(serde_json::json!({}), &validity, &records)
+    }
+  };
+
+  Ok(json_response(StatusCode::OK, &value))
+}
+
+async fn contract_sync(req: Request<Body>) -> Handled {
+  let contract_id = contract_id(&req);
+  let info = tracker(&req)
+    .and_then(|tracker| tracker.info(&contract_id))
+    .ok_or_else(|| {
+      build_error(
+        StatusCode::NOT_FOUND,
+        &format!("Contract {} is not tracked", contract_id),
+      )
+    })?;
+
+  Ok(json_response(StatusCode::OK, &serde_json::json!(info)))
+}
+
+/// Server-sent event holding `data` as JSON.
+pub fn sse_event(event: &str, id: Option<&str>, data: &Value) -> String {
+  let mut message = format!("event: {}\n", event);
+  if let Some(id) = id {
+    message.push_str(&format!("id: {}\n", id));
+  }
+  message.push_str(&format!("data: {}\n\n", data));
+  message
+}
+
+async fn contract_events(req: Request<Body>) -> Handled {
+  let contract_id = contract_id(&req);
+  let (snapshot, mut updates) = tracker(&req)
+    .and_then(|tracker| tracker.subscribe(&contract_id))
+    .ok_or_else(|| {
+      build_error(
+        StatusCode::NOT_FOUND,
+        &format!("Contract {} is not tracked", contract_id),
+      )
+    })?;
+
+  let (mut sender, body) = Body::channel();
+  tokio::spawn(async move {
+    let snapshot = sse_event(
+      "snapshot",
+      snapshot.sort_key.as_deref(),
+      &serde_json::json!(snapshot),
+    );
+    if sender.send_data(snapshot.into()).await.is_err() {
+      return;
+    }
+
+    let mut keep_alive =
+      tokio::time::interval_at(Instant::now() + KEEP_ALIVE, KEEP_ALIVE);
+    loop {
+      let message = tokio::select! {
+        update = updates.recv() => match update {
+          Ok(update) => sse_event(
+            "update",
+            update.sort_key.as_deref(),
+            &serde_json::json!(update),
+          ),
+          // Updates it missed cannot be patched over, so a subscriber that
+          // fell behind is disconnected to subscribe again from a snapshot.
+          Err(_) => break,
+        },
+        _ = keep_alive.tick() => String::from(":\n\n"),
+      };
+      if sender.send_data(message.into()).await.is_err() {
+        break;
+      }
     }
+  });
+
+  Ok(
+    Response::builder()
+      .header(CONTENT_TYPE, "text/event-stream")
+      .header(CACHE_CONTROL, "no-cache")
+      .body(body)
+      .unwrap(),
+  )
+}
+
+async fn tracked(req: Request<Body>) -> Handled {
+  let infos = tracker(&req)
+    .map(|tracker| tracker.infos())
+    .unwrap_or_default();
+
+  Ok(json_response(
+    StatusCode::OK,
+    &serde_json::json!({ "contracts": infos }),
+  ))
+}
+
+async fn contract_interactions(req: Request<Body>) -> Handled {
+  let query = ContractQuery::from_request(&req)?;
+  let contract_id = contract_id(&req);
+  let (height, cache) = (query.height, query.cache);
+  let arweave = query.arweave();
+
+  let (interactions, _, _) = evaluate(move || async move {
+    arweave.get_interactions(contract_id, height, cache).await
+  })
+  .await?
+  .map_err(|err| build_contract_error(&err))?;
+
+  let interactions: Vec<_> =
+    interactions.into_iter().map(|edge| edge.node).collect();
+  Ok(json_response(
+    StatusCode::OK,
+    &serde_json::json!({ "interactions": interactions }),
+  ))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateBlock {
+  pub height: usize,
+  pub indep_hash: String,
+  pub timestamp: usize,
+}
+
+/// Interaction of a simulation, shaped like the ones of the node bindings.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateInteraction {
+  pub id: String,
+  pub owner: String,
+  #[serde(default = "zero")]
+  pub quantity: String,
+  #[serde(default = "zero")]
+  pub reward: String,
+  #[serde(default)]
+  pub target: Option<String>,
+  #[serde(default)]
+  pub tags: Vec<GQLTagInterface>,
+  #[serde(default)]
+  pub block: Option<SimulateBlock>,
+  /// Input of the interaction, either as JSON or as a string holding it.
+  pub input: Value,
+}
+
+fn zero() -> String {
+  String::from("0")
+}
+
+impl SimulateInteraction {
+  pub fn into_transaction(self) -> GQLEdgeInterface {
+    let input = match self.input {
+      Value::String(input) => input,
+      input => input.to_string(),
+    };
+    let (height, indep_hash, timestamp) = match self.block {
+      Some(block) => (
+        Some(block.height.to_string()),
+        Some(block.indep_hash),
+        Some(block.timestamp.to_string()),
+      ),
+      None => (None, None, None),
+    };
+
+    create_simulated_transaction(
+      self.id,
+      self.owner,
+      self.quantity,
+      self.reward,
+      self.target,
+      self.tags,
+      height,
+      indep_hash,
+      timestamp,
+      input,
+    )
+  }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateSource {
+  /// Source of the contract, base64 encoded.
+  pub contract_src: String,
+  pub contract_type: ContractType,
+}
+
+/// Body of `POST /simulate`, mirroring the arguments of
+/// `simulate_contract`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateRequest {
+  pub contract_id: String,
+  #[serde(default)]
+  pub interactions: Vec<SimulateInteraction>,
+  #[serde(default)]
+  pub contract_init_state: Option<Value>,
+  #[serde(default)]
+  pub contract_source: Option<SimulateSource>,
+  #[serde(default)]
+  pub bundled_contract: Option<bool>,
+  #[serde(default)]
+  pub settings: Option<HashMap<String, Value>>,
+  #[serde(default)]
+  pub exm_context: Option<Value>,
+}
+
+async fn simulate(req: Request<Body>) -> Handled {
+  let query = ContractQuery::from_request(&req)?;
+  let request: SimulateRequest = read_json(req).await?;
+
+  let contract_source = match request.contract_source {
+    Some(source) => Some(ManualLoadedContract {
+      contract_src: base64::decode(&source.contract_src).map_err(|err| {
+        build_error(
+          StatusCode::BAD_REQUEST,
+          &format!("Invalid contractSrc: {}", err),
+        )
+      })?,
+      contract_type: source.contract_type,
+    }),
+    None => None,
+  };
+  let interactions: Vec<GQLEdgeInterface> = request
+    .interactions
+    .into_iter()
+    .map(SimulateInteraction::into_transaction)
+    .collect();
+  let cache = query.cache;
+  let arweave = query.arweave();
+
+  let execution = evaluate(move || async move {
+    simulate_contract(
+      request.contract_id,
+      request.contract_init_state.map(|state| state.to_string()),
+      interactions,
+      &arweave,
+      Some(cache),
+      request.bundled_contract,
+      request.settings,
+      request.exm_context.map(|context| context.to_string()),
+      contract_source,
+    )
+    .await
+  })
+  .await?
+  .map_err(|err| build_contract_error(&err))?;
+
+  match execution {
+    ExecuteResult::V8(data) => Ok(json_response(
+      StatusCode::OK,
+      &serde_json::json!({
+        "state": data.state,
+        "result": data.result.unwrap_or(Value::Null),
+        "validity": data.validity,
+        "interactions": data.records,
+        "exmContext": data.context,
+        "updated": data.updated,
+        "errors": data.errors,
+      }),
+    )),
+    ExecuteResult::Evm(..) => Err(build_error(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      "EVM contracts cannot be simulated",
+    )),
+  }
+}
+
+/// Body of `POST /dry-run`: a dry run file holding the source of its
+/// contract instead of a path to it.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunRequest {
+  /// Source of the contract, base64 encoded.
+  pub contract_src: String,
+  #[serde(flatten)]
+  pub dry: DryRun,
+}
+
+async fn dry_run(req: Request<Body>) -> Handled {
+  let query = ContractQuery::from_request(&req)?;
+  let request: DryRunRequest = read_json(req).await?;
+  let contract_src = base64::decode(&request.contract_src).map_err(|err| {
+    build_error(
+      StatusCode::BAD_REQUEST,
+      &format!("Invalid contractSrc: {}", err),
+    )
+  })?;
+
+  let (output, mismatches) = evaluate(move || async move {
+    evaluate_dry_run(
+      query.gateway_port,
+      query.gateway_host,
+      query.gateway_protocol,
+      &request.dry,
+      &contract_src,
+      query.show_validity,
+      None,
+      query.offline,
+      None,
+    )
+    .await
+  })
+  .await?
+  .map_err(|err| build_any_error(&err))?;
+
+  Ok(json_response(
+    StatusCode::OK,
+    &serde_json::json!({
+      "output": output,
+      "mismatches": mismatches,
+    }),
+  ))
+}
+
+// Kept from when it was the only endpoint. Takes the contract through the
+// `contractId` parameter instead of the path.
+async fn legacy_evaluate(req: Request<Body>) -> Handled {
+  let query = ContractQuery::from_request(&req)?;
+  let contract_id = url::form_urlencoded::parse(
+    req.uri().query().unwrap_or_default().as_bytes(),
+  )
+  .find(|(name, _)| name == "contractId")
+  .map(|(_, id)| id.into_owned())
+  .ok_or_else(|| {
+    build_error(
+      StatusCode::BAD_REQUEST,
+      "contractId was not provided in query parameters. A contract id must be provided.",
+    )
+  })?;
+
+  let tracker = tracker(&req);
+  state(contract_id, query, tracker).await
+}
+
+async fn not_found(req: Request<Body>) -> Handled {
+  Err(build_error(
+    StatusCode::NOT_FOUND,
+    &format!("No endpoint for {} {}", req.method(), req.uri().path()),
+  ))
+}
+
+async fn respond<F>(handled: F) -> Result<Response<Body>, Infallible>
+where
+  F: Future<Output = Handled>,
+{
+  Ok(handled.await.unwrap_or_else(|response| response))
+}
+
+pub fn router(tracker: Arc<Tracker>) -> Router<Body, Infallible> {
+  Router::builder()
+    .data(tracker)
+    .get("/health", |req| respond(health(req)))
+    .get("/tracked", |req| respond(tracked(req)))
+    .get("/contracts/:id/state", |req| respond(contract_state(req)))
+    .get("/contracts/:id/validity", |req| {
+      respond(contract_validity(req))
+    })
+    .get("/contracts/:id/interactions", |req| {
+      respond(contract_interactions(req))
+    })
+    .get("/contracts/:id/sync", |req| respond(contract_sync(req)))
+    .get("/contracts/:id/events", |req| respond(contract_events(req)))
+    .post("/simulate", |req| respond(simulate(req)))
+    .post("/dry-run", |req| respond(dry_run(req)))
+    .get("/evaluate", |req| respond(legacy_evaluate(req)))
+    .any(|req| respond(not_found(req)))
+    .build()
+    .unwrap()
 }
 
 pub async fn start_local_server(config: ServerConfiguration) {
   let addr = SocketAddr::from((config.host, config.port));
-  let service =
-    make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(echo)) });
+  let tracker = Arc::new(Tracker::new(
+    config.tracked,
+    config.gateway,
+    config.sync_interval,
+  ));
+  if !tracker.is_empty() {
+    spawn_sync(tracker.clone(), Handle::current());
+  }
+  let service = RouterService::new(router(tracker)).unwrap();
 
-  println!("Serving {}", addr.to_string());
+  println!("Serving {}", addr);
   println!(
     "{}",
     indoc! {
     "
        Endpoints:
-         GET   /evaluate   Evaluates a contract state given a contract id
-               ?contractId   Id of contract to be evaluated   (Required)   [string]
+         GET    /health                       Whether the server is up, along with its version
+         GET    /contracts/:id/state          Evaluates the state of a contract
+         GET    /contracts/:id/validity       Evaluates a contract, responding with the validity of its interactions
+         GET    /contracts/:id/interactions   Interactions of a contract
+         GET    /contracts/:id/sync           Sync status of a tracked contract
+         GET    /contracts/:id/events         Stream of the state updates of a tracked contract, as server-sent events
+         GET    /tracked                      Sync status of every tracked contract
+         POST   /simulate                     Evaluates a contract with the interactions, source and EXM context of the JSON body
+         POST   /dry-run                      Evaluates the dry run of the JSON body
+
+       Query parameters:
                ?height   Height to be used during evaluation   [number]
                ?gatewayHost   Gateway to be used for and during evaluation   (Default: arweave.net)   [string]
                ?gatewayPort   Port to be used for gateway communication   (Default: 443)   [number]
                ?gatewayProtocol   Protocol to be used for gateway communication   (Default: https)   [string]
-               ?showValidity   Whether validity table should be included in the JSON response   (Default: false)   [boolean]
+               ?showValidity   Whether validity table and interaction records should be included in the JSON response   (Default: false)   [boolean]
                ?cache   Whether built-in cache system should be used during execution   (Default: true)   [boolean]
                ?showErrors   Whether server console should print out execution exceptions   (Default: false)   [boolean]
+               ?verifySignatures   Whether contract and interaction signatures should be verified   (Default: false)   [boolean]
+               ?verifyData   Whether contract sources and init states should be verified against their data_root   (Default: false)   [boolean]
+               ?enforceRequirements   Whether interactions should meet the contract's Min-Fee, Interaction-Target and Min-Quantity tags   (Default: false)   [boolean]
+               ?offline   Whether dry runs should be served from the fixtures of their file   (Default: false)   [boolean]
       "}
   );
 
-  let server = Server::bind(&addr).executor(LocalExec).serve(service);
-  server.await.unwrap();
+  let server = Server::bind(&addr).serve(service);
+  if let Err(err) = server.await {
+    eprintln!("Server error: {}", err);
+  }
 }
 
-#[derive(Clone, Copy, Debug)]
-struct LocalExec;
+#[cfg(test)]
+mod tests {
+  use crate::cli::parse::Gateway;
+  use crate::local_server::{
+    sse_event, ContractQuery, DryRunRequest, SimulateRequest,
+  };
+  use hyper::StatusCode;
+  use three_em_arweave::miscellaneous::ContractType;
 
-impl<F> hyper::rt::Executor<F> for LocalExec
-where
-  F: std::future::Future + 'static, // not requiring `Send`
-{
-  fn execute(&self, fut: F) {
-    // This will spawn into the currently running `LocalSet`.
-    tokio::task::spawn_local(fut);
+  #[test]
+  fn test_contract_query() {
+    let query = ContractQuery::parse(Some(
+      "height=749180&gatewayHost=localhost&gatewayPort=1984&cache=false",
+    ))
+    .unwrap();
+    assert_eq!(query.height, Some(749180));
+    assert_eq!(query.gateway_host, "localhost");
+    assert_eq!(query.gateway_port, 1984);
+    assert_eq!(query.gateway_protocol, "https");
+    assert!(!query.cache);
+    assert!(ContractQuery::parse(None).unwrap().cache);
+
+    let err = ContractQuery::parse(Some("height=latest")).unwrap_err();
+    assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[test]
+  fn test_tracked_query() {
+    let gateway = Gateway {
+      host: String::from("arweave.net"),
+      port: 443,
+      protocol: String::from("https"),
+    };
+    let tracked = |query: &str| {
+      ContractQuery::parse(Some(query))
+        .unwrap()
+        .is_tracked(&gateway)
+    };
+
+    assert!(ContractQuery::parse(None).unwrap().is_tracked(&gateway));
+    assert!(tracked("showValidity=true"));
+    assert!(!tracked("height=749180"));
+    assert!(!tracked("gatewayHost=localhost"));
+    assert!(!tracked("gatewayPort=1984"));
+    assert!(!tracked("cache=false"));
+    assert!(!tracked("showErrors=true"));
+    assert!(!tracked("enforceRequirements=true"));
+  }
+
+  #[test]
+  fn test_simulate_request() {
+    let request: SimulateRequest = serde_json::from_value(serde_json::json!({
+      "contractId": "contract",
+      "contractInitState": { "counter": 0 },
+      "interactions": [{
+        "id": "tx1",
+        "owner": "Andres",
+        "input": { "function": "increment" },
+        "block": { "height": 10, "indepHash": "block", "timestamp": 1 },
+      }, {
+        "id": "tx2",
+        "owner": "Divy",
+        "input": "{\"function\":\"increment\"}",
+        "tags": [{ "name": "App-Name", "value": "3em" }],
+      }],
+    }))
+    .unwrap();
+
+    let transactions: Vec<_> = request
+      .interactions
+      .into_iter()
+      .map(|interaction| interaction.into_transaction())
+      .collect();
+    assert_eq!(transactions[0].node.block.height, 10);
+    assert_eq!(transactions[0].node.owner.address, "Andres");
+    let input = |index: usize| {
+      let tags = &transactions[index].node.tags;
+      tags
+        .iter()
+        .find(|tag| tag.name == "Input")
+        .unwrap()
+        .value
+        .to_owned()
+    };
+    assert_eq!(input(0), input(1));
+    assert_eq!(transactions[1].node.tags.len(), 2);
+  }
+
+  #[test]
+  fn test_dry_run_request() {
+    let request: DryRunRequest = serde_json::from_value(serde_json::json!({
+      "contractType": "JAVASCRIPT",
+      "contractSrc": "ZXhwb3J0IC4uLg==",
+      "initialState": { "users": [] },
+      "interactions": [{ "id": "tx1", "caller": "Andres", "input": {} }],
+    }))
+    .unwrap();
+    assert_eq!(
+      base64::decode(&request.contract_src).unwrap(),
+      b"export ..."
+    );
+    assert!(matches!(
+      request.dry.contract_type,
+      ContractType::JAVASCRIPT
+    ));
+    assert_eq!(request.dry.interactions.len(), 1);
+
+    // Sources are never read from the filesystem of the server.
+    assert!(serde_json::from_value::<DryRunRequest>(serde_json::json!({
+      "contractType": "JAVASCRIPT",
+      "contractSource": "/etc/passwd",
+      "initialState": {},
+      "interactions": [],
+    }))
+    .is_err());
+  }
+
+  #[test]
+  fn test_sse_event() {
+    assert_eq!(
+      sse_event("update", Some("key"), &serde_json::json!({ "a": "b\nc" })),
+      "event: update\nid: key\ndata: {\"a\":\"b\\nc\"}\n\n"
+    );
+    assert_eq!(
+      sse_event("snapshot", None, &serde_json::json!(null)),
+      "event: snapshot\ndata: null\n\n"
+    );
   }
 }
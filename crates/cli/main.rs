@@ -6,28 +6,41 @@
  *
  */
 // Imports the moduls in the sister files next to main
+mod batch;
+mod cache;
 mod cli;
 mod core_nodes;
+mod debugger;
+mod diff;
 mod dry_run;
+mod evm_dry_run;
+mod expectations;
+mod json_path;
 mod local_server;
 mod messages;
 mod node;
-mod print_help;
+mod output;
 mod run;
+mod tracker;
 mod utils;
+mod watch;
 
-use crate::cli::parse;
-use crate::cli::parse::{Flags, ParseResult};
+use crate::cli::config::Config;
+use crate::cli::parse::{Cli, Flags, Gateway};
+use clap::Parser;
 use deno_core::error::AnyError;
 
 use crate::local_server::{start_local_server, ServerConfiguration};
 use std::env;
-use std::net::IpAddr;
-use std::ops::Deref;
-use std::str::FromStr;
 
 fn main() -> Result<(), AnyError> {
-  let parse_result = parse::parse()?;
+  let cli = Cli::parse();
+  let config = Config::load(cli.config.as_deref())?;
+
+  // The cache of `three_em_arweave` is rooted at this variable.
+  if let Some(cache_dir) = cli.cache_dir.or_else(|| config.cache_dir.clone()) {
+    env::set_var("THREE_EM_CACHE_DIR", cache_dir);
+  }
 
   /**
    * @Runtime
@@ -57,99 +70,164 @@ fn main() -> Result<(), AnyError> {
    */
   let rt = tokio::runtime::Runtime::new()?;
 
-  match parse_result {
-    ParseResult::Help { cmd } => {
-      print_help::print_help(Some(cmd.deref())); //Prints message containing list of cmds, options etc.
-    }
-
-    ParseResult::Known { flag } => {
-      // Match whether user wants Run, DryRun, Serve
-      match flag {
-        Flags::Run {
-          port,
-          host,
-          protocol,
-          tx,
-          pretty_print,
+  // Match whether user wants Run, DryRun, Diff, Serve, Cache
+  match cli.flags {
+    Flags::Run {
+      tx,
+      contracts_file,
+      concurrency,
+      gateway,
+      output,
+      select,
+      no_print,
+      show_validity,
+      save,
+      benchmark,
+      height,
+      no_cache,
+      show_errors,
+      verify_signatures,
+      verify_data,
+      enforce_requirements,
+      trace,
+      limits,
+    } => {
+      let gateway = gateway.resolve(&config);
+      match contracts_file {
+        Some(contracts_file) => batch::run_batch(
+          rt.handle(),
+          gateway.port,
+          gateway.host,
+          gateway.protocol,
+          batch::read_contract_ids(&contracts_file)?,
+          concurrency as usize,
+          show_validity,
+          height,
+          no_cache,
+          show_errors,
+          verify_signatures,
+          verify_data,
+          enforce_requirements,
+          limits.resolve(&config),
+        )?,
+        //run a new Arweave object w/ cache established - blocking the thread until future task complete
+        None => rt.block_on(run::run(
+          gateway.port,
+          gateway.host,
+          gateway.protocol,
+          tx.expect("--contract-id is required without --contracts-file"),
+          output.resolve(&config),
+          select,
           no_print,
           show_validity,
           save,
-          save_path,
           benchmark,
           height,
           no_cache,
           show_errors,
-        } => {
-          if tx.is_none() {
-            print_help::print_help(Some("run"));
-            println!("{}", "Option '--contract-id' is required");
-          } else {
-            //run a new Arweave object w/ cache established - blocking the thread until future task complete
-            rt.block_on(run::run(
-              port,
-              host,
-              protocol,
-              tx.unwrap(),
-              pretty_print,
-              no_print,
-              show_validity,
-              save,
-              benchmark,
-              save_path,
-              height,
-              no_cache,
-              show_errors,
-            ))?;
-          }
-        }
-        Flags::DryRun {
-          // Used to test code
-          host,
-          port,
-          protocol,
-          pretty_print,
+          verify_signatures,
+          verify_data,
+          enforce_requirements,
+          trace,
+          limits.resolve(&config),
+        ))?,
+      }
+    }
+    Flags::DryRun {
+      // Used to test code
+      file,
+      gateway,
+      output,
+      show_validity,
+      trace,
+      interactive,
+      offline,
+      watch,
+      limits,
+    } => {
+      let gateway = gateway.resolve(&config);
+      let heap_limit = limits.resolve(&config);
+      if interactive {
+        rt.block_on(dry_run::dry_run_interactive(
+          gateway.port,
+          gateway.host,
+          gateway.protocol,
+          file,
+          offline,
+          heap_limit,
+        ))?;
+      } else if watch {
+        rt.block_on(watch::dry_run_watch(
+          gateway.port,
+          gateway.host,
+          gateway.protocol,
+          file,
+          trace,
+          offline,
+          heap_limit,
+        ))?;
+      } else {
+        rt.block_on(dry_run::dry_run(
+          gateway.port,
+          gateway.host,
+          gateway.protocol,
+          output.resolve(&config),
           show_validity,
           file,
-        } => {
-          if file.is_none() {
-            print_help::print_help(Some("dry-run"));
-            println!("{}", "Option '--file' is required");
-          } else {
-            rt.block_on(dry_run::dry_run(
-              port,
-              host,
-              protocol,
-              pretty_print,
-              show_validity,
-              file.unwrap(),
-            ))?;
-          }
-        }
-        Flags::Serve {
-          //Spins up a local testnet
-          server_port,
-          server_host,
-        } => {
-          let ip_addr = IpAddr::from_str(server_host.as_str());
-          if let Err(_) = ip_addr {
-            print_help::print_help(Some("serve"));
-            println!("{}", "Invalid IP Address provided in '--server-host'");
-          } else {
-            // Spawn the !Send future in the currently running
-            // local task set.
-            let local = tokio::task::LocalSet::new();
-            local.block_on(
-              &rt,
-              start_local_server(ServerConfiguration {
-                host: ip_addr.unwrap(),
-                port: server_port,
-              }),
-            );
-          }
+          trace,
+          offline,
+          heap_limit,
+        ))?;
+      }
+    }
+    Flags::Diff {
+      tx,
+      from,
+      to,
+      gateway,
+      output,
+      no_cache,
+      show_errors,
+      limits,
+    } => {
+      let gateway = gateway.resolve(&config);
+      rt.block_on(diff::diff(
+        gateway.port,
+        gateway.host,
+        gateway.protocol,
+        tx,
+        from,
+        to,
+        output.resolve(&config),
+        no_cache,
+        show_errors,
+        limits.resolve(&config),
+      ))?;
+    }
+    Flags::Serve {
+      //Spins up a local testnet
+      server_port,
+      server_host,
+      tracked,
+      sync_interval,
+    } => {
+      let mut tracked_contracts = config.track.to_owned().unwrap_or_default();
+      for contract_id in tracked {
+        if !tracked_contracts.contains(&contract_id) {
+          tracked_contracts.push(contract_id);
         }
-      };
+      }
+
+      rt.block_on(start_local_server(ServerConfiguration {
+        host: server_host,
+        port: server_port,
+        tracked: tracked_contracts,
+        gateway: Gateway::from_env(&config)?,
+        sync_interval,
+      }));
     }
-  }
+    Flags::Cache { command } => cache::cache(command, &config)?,
+  };
 
   Ok(())
 }
@@ -0,0 +1,147 @@
+use crate::cli::parse::OutputFormat;
+use deno_core::error::AnyError;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use three_em_evm::{Storage, U256};
+
+/// Writes `value` in `format` as it is serialized, without building the
+/// whole output in memory first. Text formats end with a newline.
+pub fn write_value<W: Write>(
+  mut writer: W,
+  value: &Value,
+  format: OutputFormat,
+) -> Result<(), AnyError> {
+  match format {
+    OutputFormat::Json => {
+      serde_json::to_writer(&mut writer, value)?;
+      writeln!(writer)?;
+    }
+    OutputFormat::Pretty => {
+      serde_json::to_writer_pretty(&mut writer, value)?;
+      writeln!(writer)?;
+    }
+    OutputFormat::Jsonl => {
+      let values = match value {
+        Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+      };
+      for value in values {
+        serde_json::to_writer(&mut writer, value)?;
+        writeln!(writer)?;
+      }
+    }
+    OutputFormat::Cbor => ciborium::ser::into_writer(value, &mut writer)
+      .map_err(|err| AnyError::msg(err.to_string()))?,
+    OutputFormat::Msgpack => rmp_serde::encode::write(&mut writer, value)?,
+    OutputFormat::Table => {
+      return Err(AnyError::msg(
+        "Table output is only available when running EVM contracts",
+      ))
+    }
+  }
+
+  writer.flush()?;
+  Ok(())
+}
+
+/// Storage and return data of an EVM contract, decoded into aligned
+/// columns.
+pub fn evm_table(storage: &Storage, result: &[u8]) -> String {
+  let mut rows = vec![[
+    String::from("ACCOUNT"),
+    String::from("SLOT"),
+    String::from("VALUE"),
+    String::from("DECIMAL"),
+  ]];
+
+  let accounts: BTreeMap<&U256, BTreeMap<&U256, &U256>> = storage
+    .inner
+    .iter()
+    .map(|(account, slots)| (account, slots.iter().collect()))
+    .collect();
+  for (account, slots) in accounts {
+    for (slot, value) in slots {
+      rows.push([
+        format!("{:#x}", account),
+        format!("{:#x}", slot),
+        format!("{:#x}", value),
+        value.to_string(),
+      ]);
+    }
+  }
+
+  let mut widths = [0; 4];
+  for row in &rows {
+    for (width, cell) in widths.iter_mut().zip(row) {
+      *width = (*width).max(cell.len());
+    }
+  }
+
+  let mut table = format!("RESULT  0x{}\n\n", hex::encode(result));
+  for row in &rows {
+    let line = row
+      .iter()
+      .zip(widths)
+      .map(|(cell, width)| format!("{:width$}", cell, width = width))
+      .collect::<Vec<_>>()
+      .join("  ");
+    table.push_str(line.trim_end());
+    table.push('\n');
+  }
+  table
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cli::parse::OutputFormat;
+  use crate::output::{evm_table, write_value};
+  use serde_json::{json, Value};
+  use three_em_evm::{Storage, U256};
+
+  fn write(value: &Value, format: OutputFormat) -> Vec<u8> {
+    let mut output = Vec::new();
+    write_value(&mut output, value, format).unwrap();
+    output
+  }
+
+  #[test]
+  fn test_write_value() {
+    let value = json!([{ "name": "Andres" }, 2]);
+
+    assert_eq!(
+      write(&value, OutputFormat::Json),
+      b"[{\"name\":\"Andres\"},2]\n"
+    );
+    assert_eq!(
+      write(&value, OutputFormat::Jsonl),
+      b"{\"name\":\"Andres\"}\n2\n"
+    );
+    assert_eq!(write(&json!(1), OutputFormat::Jsonl), b"1\n");
+
+    let cbor = write(&value, OutputFormat::Cbor);
+    let decoded: Value = ciborium::de::from_reader(cbor.as_slice()).unwrap();
+    assert_eq!(decoded, value);
+
+    let msgpack = write(&value, OutputFormat::Msgpack);
+    let decoded: Value = rmp_serde::from_slice(&msgpack).unwrap();
+    assert_eq!(decoded, value);
+
+    assert!(write_value(Vec::new(), &value, OutputFormat::Table).is_err());
+  }
+
+  #[test]
+  fn test_evm_table() {
+    let mut storage = Storage::new(U256::zero());
+    storage.insert(&U256::zero(), U256::from(1), U256::from(255));
+    storage.insert(&U256::zero(), U256::zero(), U256::from(2));
+
+    assert_eq!(
+      evm_table(&storage, &[0, 2]),
+      "RESULT  0x0002\n\n\
+       ACCOUNT  SLOT  VALUE  DECIMAL\n\
+       0x0      0x0   0x2    2\n\
+       0x0      0x1   0xff   255\n"
+    );
+  }
+}
@@ -1,10 +1,65 @@
+use crate::cli::parse::OutputFormat;
+use crate::json_path;
+use crate::output::{evm_table, write_value};
 use deno_core::error::AnyError;
-use std::io::Write;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use three_em_arweave::arweave::Arweave;
 use three_em_arweave::cache::ArweaveCache;
 use three_em_arweave::cache::CacheExt;
 use three_em_executor::execute_contract;
-use three_em_executor::executor::ExecuteResult;
+use three_em_executor::executor::{with_validity, ExecuteResult};
+use three_em_executor::trace::write_trace;
+
+/// What `run` outputs for an evaluated contract: its state, or the store
+/// and return data of EVM contracts.
+pub fn run_output(execution: ExecuteResult, show_validity: bool) -> Value {
+  match execution {
+    ExecuteResult::V8(data) => {
+      if show_validity {
+        with_validity(
+          serde_json::json!({
+            "state": data.state,
+            "evolutions": data.evolutions,
+            "result": data.result.unwrap_or(Value::Null),
+          }),
+          &data.validity,
+          &data.records,
+        )
+      } else {
+        data.state
+      }
+    }
+    ExecuteResult::Evm(store, result, validity_table, records) => {
+      let value = serde_json::json!({
+        "result": hex::encode(result),
+        "store": hex::encode(store.raw()),
+      });
+      if show_validity {
+        with_validity(value, &validity_table, &records)
+      } else {
+        value
+      }
+    }
+  }
+}
+
+fn write_run_output<W: Write>(
+  mut writer: W,
+  value: &Value,
+  table: Option<&str>,
+  output: OutputFormat,
+) -> Result<(), AnyError> {
+  match table {
+    Some(table) => {
+      writer.write_all(table.as_bytes())?;
+      writer.flush()?;
+      Ok(())
+    }
+    None => write_value(writer, value, output),
+  }
+}
 
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
@@ -12,23 +67,40 @@ pub async fn run(
   host: String,
   protocol: String,
   tx: String,
-  pretty_print: bool,
+  output: OutputFormat,
+  select: Option<String>,
   no_print: bool,
   show_validity: bool,
-  save: bool,
+  save: Option<String>,
   benchmark: bool,
-  save_path: String,
   height: Option<usize>,
   no_cache: bool,
   show_errors: bool,
+  verify_signatures: bool,
+  verify_data: bool,
+  enforce_requirements: bool,
+  trace: Option<String>,
+  heap_limit: Option<usize>,
 ) -> Result<(), AnyError> {
+  if select.is_some() && output == OutputFormat::Table {
+    return Err(AnyError::msg("--select cannot be used with table output"));
+  }
+
   // Create a new Arweave Object with a new cache
-  let arweave = Arweave::new(port, host, protocol, ArweaveCache::new());
+  let arweave = Arweave::new(port, host, protocol, ArweaveCache::new())
+    .with_signature_verification(verify_signatures)
+    .with_data_verification(verify_data)
+    .with_requirement_enforcement(enforce_requirements)
+    .with_tracing(trace.is_some())
+    .with_heap_limit(heap_limit);
   let start = std::time::Instant::now();
 
+  // A trace covers every interaction, so it cannot start from a cached state.
+  let cache = !no_cache && trace.is_none();
+
   //Run contract based on contract id - this is only a runtime so no input is sent here
   let execution: ExecuteResult =
-    execute_contract(tx, height, !no_cache, show_errors, None, None, &arweave)
+    execute_contract(tx, height, cache, show_errors, None, None, &arweave)
       .await?;
 
   if benchmark {
@@ -36,62 +108,40 @@ pub async fn run(
     println!("Took {}ms to execute contract", elapsed.as_millis());
   }
 
-  match execution {
-    ExecuteResult::V8(data) => {
-      let state = data.state;
-      let validity_table = data.validity;
-      let result = data.result.unwrap_or(serde_json::Value::Null);
-      // return state and result when endpoint is reached.
-      let value = if show_validity {
-        serde_json::json!({
-            "state": state,
-            "validity": validity_table,
-            "result": result
-        })
-      } else {
-        state
-      };
-
-      if !no_print {
-        if pretty_print {
-          println!("{}", serde_json::to_string_pretty(&value).unwrap());
-        } else {
-          println!("{}", value);
-        }
+  if let Some(path) = trace {
+    match &execution {
+      ExecuteResult::V8(data) => write_trace(File::create(path)?, &data.trace)?,
+      ExecuteResult::Evm(..) => {
+        return Err(AnyError::msg(
+          "Traces are only recorded for JavaScript and WASM contracts",
+        ))
       }
+    }
+  }
 
-      if save {
-        let mut file = std::fs::File::create(save_path).unwrap();
-        file
-          .write_all(serde_json::to_vec(&value).unwrap().as_slice())
-          .unwrap();
-      }
+  let table = match (&execution, output) {
+    (ExecuteResult::Evm(store, result, ..), OutputFormat::Table) => {
+      Some(evm_table(store, result))
     }
-    ExecuteResult::Evm(store, result, validity_table) => {
-      let store = hex::encode(store.raw());
-      let result = hex::encode(result);
+    _ => None,
+  };
+  let mut value = run_output(execution, show_validity);
+  if let Some(selector) = &select {
+    value = json_path::select(&value, selector)?;
+  }
 
-      let value = if show_validity {
-        serde_json::json!({
-          "result": result,
-          "store": store,
-          "validity": validity_table
-        })
-      } else {
-        serde_json::json!({
-          "result": result,
-          "store": store,
-        })
-      };
+  if !no_print {
+    write_run_output(
+      std::io::stdout().lock(),
+      &value,
+      table.as_deref(),
+      output,
+    )?;
+  }
 
-      if !no_print {
-        if pretty_print {
-          println!("{}", serde_json::to_string_pretty(&value).unwrap());
-        } else {
-          println!("{}", value);
-        }
-      }
-    }
+  if let Some(save_path) = save {
+    let file = BufWriter::new(File::create(save_path)?);
+    write_run_output(file, &value, table.as_deref(), output)?;
   }
 
   Ok(())
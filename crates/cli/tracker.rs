@@ -0,0 +1,451 @@
+use crate::cli::parse::Gateway;
+use indexmap::map::IndexMap;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use three_em_arweave::arweave::{get_cache, Arweave};
+use three_em_arweave::cache::{ArweaveCache, CacheExt};
+use three_em_arweave::miscellaneous::get_sort_key;
+use three_em_executor::executor::ExecuteResult;
+use three_em_executor::json_patch::{diff, PatchOperation};
+use three_em_executor::{
+  execute_contract, invalidate_result, ContractError, ValidityTable,
+};
+use tokio::runtime::Handle;
+use tokio::sync::broadcast;
+
+/// Updates a subscriber may fall behind by before being disconnected.
+const UPDATES_CAPACITY: usize = 64;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStatus {
+  /// Not evaluated yet.
+  Pending,
+  Syncing,
+  Synced,
+  /// The last sync failed. The state of the sync before it, if any, is
+  /// still served.
+  Failed,
+}
+
+/// Sync status of a tracked contract.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncInfo {
+  pub contract_id: String,
+  pub status: SyncStatus,
+  /// Gateway height when the served state was last evaluated.
+  pub height: Option<usize>,
+  /// Sort key of the last interaction of the served state.
+  pub sort_key: Option<String>,
+  /// Number of interactions evaluated so far.
+  pub interactions: usize,
+  /// When the served state was last evaluated, in seconds since the Unix
+  /// epoch.
+  pub synced_at: Option<u64>,
+  /// How long the last sync took.
+  pub elapsed_ms: Option<u64>,
+  pub error: Option<String>,
+}
+
+/// Change a sync brought to the state of a tracked contract.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StateUpdate {
+  pub contract_id: String,
+  pub sort_key: Option<String>,
+  pub height: Option<usize>,
+  /// Validity of the interactions evaluated since the previous update.
+  pub validity: ValidityTable,
+  /// JSON Patch turning the previous state into the new one.
+  pub patch: Vec<PatchOperation>,
+}
+
+/// State of a tracked contract when subscribing to its updates, which
+/// apply on top of it. `state` is `null` until the first sync.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot {
+  pub contract_id: String,
+  pub sort_key: Option<String>,
+  pub height: Option<usize>,
+  pub state: Value,
+}
+
+struct TrackedContract {
+  info: SyncInfo,
+  execution: Option<ExecuteResult>,
+  updates: broadcast::Sender<StateUpdate>,
+}
+
+/// Contracts the server keeps evaluated, polling the gateway for their new
+/// interactions every `interval`.
+pub struct Tracker {
+  contracts: Mutex<IndexMap<String, TrackedContract>>,
+  gateway: Gateway,
+  interval: Duration,
+}
+
+fn validity(execution: &ExecuteResult) -> &ValidityTable {
+  match execution {
+    ExecuteResult::V8(data) => &data.validity,
+    ExecuteResult::Evm(_, _, validity, _) => validity,
+  }
+}
+
+/// State updates are diffed on: the state of JavaScript and WASM
+/// contracts, or the hex encoded storage of EVM ones.
+fn state(execution: &ExecuteResult) -> Value {
+  match execution {
+    ExecuteResult::V8(data) => data.state.to_owned(),
+    ExecuteResult::Evm(store, ..) => Value::String(hex::encode(store.raw())),
+  }
+}
+
+/// Sort key of the last cached interaction of a contract.
+fn last_sort_key(contract_id: &str) -> Option<String> {
+  let interactions = get_cache()
+    .lock()
+    .unwrap()
+    .find_interactions(contract_id.to_owned())?;
+  let last = &interactions.last()?.node;
+  Some(get_sort_key(&last.block.height, &last.block.id, &last.id))
+}
+
+impl Tracker {
+  pub fn new(
+    contract_ids: Vec<String>,
+    gateway: Gateway,
+    interval: Duration,
+  ) -> Tracker {
+    let contracts = contract_ids
+      .into_iter()
+      .map(|contract_id| {
+        let info = SyncInfo {
+          contract_id: contract_id.to_owned(),
+          status: SyncStatus::Pending,
+          height: None,
+          sort_key: None,
+          interactions: 0,
+          synced_at: None,
+          elapsed_ms: None,
+          error: None,
+        };
+        (
+          contract_id,
+          TrackedContract {
+            info,
+            execution: None,
+            updates: broadcast::channel(UPDATES_CAPACITY).0,
+          },
+        )
+      })
+      .collect();
+
+    Tracker {
+      contracts: Mutex::new(contracts),
+      gateway,
+      interval,
+    }
+  }
+
+  /// Gateway the contracts are synced through.
+  pub fn gateway(&self) -> &Gateway {
+    &self.gateway
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.contracts.lock().unwrap().is_empty()
+  }
+
+  pub fn infos(&self) -> Vec<SyncInfo> {
+    let contracts = self.contracts.lock().unwrap();
+    contracts
+      .values()
+      .map(|contract| contract.info.to_owned())
+      .collect()
+  }
+
+  pub fn info(&self, contract_id: &str) -> Option<SyncInfo> {
+    let contracts = self.contracts.lock().unwrap();
+    contracts
+      .get(contract_id)
+      .map(|contract| contract.info.to_owned())
+  }
+
+  /// Last evaluation of a tracked contract, if it was evaluated yet.
+  pub fn execution(&self, contract_id: &str) -> Option<ExecuteResult> {
+    let contracts = self.contracts.lock().unwrap();
+    contracts.get(contract_id)?.execution.to_owned()
+  }
+
+  /// Current state of a tracked contract, along with a receiver of the
+  /// updates made to it from then on.
+  pub fn subscribe(
+    &self,
+    contract_id: &str,
+  ) -> Option<(StateSnapshot, broadcast::Receiver<StateUpdate>)> {
+    let contracts = self.contracts.lock().unwrap();
+    let contract = contracts.get(contract_id)?;
+    let snapshot = StateSnapshot {
+      contract_id: contract_id.to_owned(),
+      sort_key: contract.info.sort_key.to_owned(),
+      height: contract.info.height,
+      state: contract
+        .execution
+        .as_ref()
+        .map(state)
+        .unwrap_or(Value::Null),
+    };
+
+    Some((snapshot, contract.updates.subscribe()))
+  }
+
+  fn set_status(&self, contract_id: &str, status: SyncStatus) {
+    if let Some(contract) = self.contracts.lock().unwrap().get_mut(contract_id)
+    {
+      contract.info.status = status;
+    }
+  }
+
+  /// Records the outcome of a sync started at gateway `height`, sending
+  /// subscribers what changed if it evaluated new interactions.
+  pub fn record(
+    &self,
+    contract_id: &str,
+    execution: Result<ExecuteResult, ContractError>,
+    height: Option<usize>,
+    sort_key: Option<String>,
+    elapsed: Duration,
+  ) {
+    let mut contracts = self.contracts.lock().unwrap();
+    let contract = match contracts.get_mut(contract_id) {
+      Some(contract) => contract,
+      None => return,
+    };
+    contract.info.elapsed_ms = Some(elapsed.as_millis() as u64);
+
+    match execution {
+      Ok(execution) => {
+        let previous = contract.execution.as_ref();
+        let new_validity: ValidityTable = validity(&execution)
+          .iter()
+          .filter(|(id, _)| {
+            previous.map_or(true, |previous| {
+              !validity(previous).contains_key(id.as_str())
+            })
+          })
+          .map(|(id, validity)| (id.to_owned(), validity.to_owned()))
+          .collect();
+        let patch = diff(
+          &previous.map(state).unwrap_or(Value::Null),
+          &state(&execution),
+        );
+        if !new_validity.is_empty() || !patch.is_empty() {
+          // Nobody may be subscribed.
+          let _ = contract.updates.send(StateUpdate {
+            contract_id: contract_id.to_owned(),
+            sort_key: sort_key.to_owned(),
+            height,
+            validity: new_validity,
+            patch,
+          });
+        }
+
+        contract.info.status = SyncStatus::Synced;
+        contract.info.height = height;
+        contract.info.sort_key = sort_key;
+        contract.info.interactions = validity(&execution).len();
+        contract.info.synced_at = SystemTime::now()
+          .duration_since(UNIX_EPOCH)
+          .map(|elapsed| elapsed.as_secs())
+          .ok();
+        contract.info.error = None;
+        contract.execution = Some(execution);
+      }
+      Err(err) => {
+        contract.info.status = SyncStatus::Failed;
+        contract.info.error = Some(err.to_string());
+      }
+    }
+  }
+
+  /// Evaluates the interactions a contract received since its last sync,
+  /// starting from its cached state.
+  async fn sync(&self, arweave: &Arweave, contract_id: &str) {
+    self.set_status(contract_id, SyncStatus::Syncing);
+    let start = Instant::now();
+
+    let height = arweave
+      .get_network_info()
+      .await
+      .ok()
+      .map(|info| info.height);
+    invalidate_result(contract_id);
+    let execution = execute_contract(
+      contract_id.to_owned(),
+      None,
+      true,
+      false,
+      None,
+      None,
+      arweave,
+    )
+    .await;
+    let sort_key = last_sort_key(contract_id);
+
+    self.record(contract_id, execution, height, sort_key, start.elapsed());
+  }
+
+  async fn run(&self) {
+    let arweave = Arweave::new(
+      self.gateway.port,
+      self.gateway.host.to_owned(),
+      self.gateway.protocol.to_owned(),
+      ArweaveCache::new(),
+    );
+    let contract_ids: Vec<String> =
+      self.contracts.lock().unwrap().keys().cloned().collect();
+
+    loop {
+      for contract_id in &contract_ids {
+        self.sync(&arweave, contract_id).await;
+      }
+      tokio::time::sleep(self.interval).await;
+    }
+  }
+}
+
+/// Syncs the contracts of `tracker` on a thread of their own, since
+/// runtimes are not `Send`.
+pub fn spawn_sync(tracker: Arc<Tracker>, runtime: Handle) {
+  std::thread::Builder::new()
+    .name(String::from("contract-sync"))
+    .spawn(move || runtime.block_on(tracker.run()))
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cli::parse::Gateway;
+  use crate::tracker::{SyncStatus, Tracker};
+  use serde_json::Value;
+  use std::time::Duration;
+  use three_em_evm::{Storage, U256};
+  use three_em_executor::executor::ExecuteResult;
+  use three_em_executor::json_patch::PatchOperation;
+  use three_em_executor::{ContractError, ValidityRecords, ValidityTable};
+
+  fn tracker() -> Tracker {
+    Tracker::new(
+      vec![String::from("contract")],
+      Gateway {
+        host: String::from("arweave.net"),
+        port: 443,
+        protocol: String::from("https"),
+      },
+      Duration::from_secs(30),
+    )
+  }
+
+  fn evm_execution(interactions: &[&str], value: u64) -> ExecuteResult {
+    let mut storage = Storage::new(U256::zero());
+    storage.insert(&U256::zero(), U256::zero(), U256::from(value));
+    let mut validity = ValidityTable::new();
+    for id in interactions {
+      validity.insert(id.to_string(), serde_json::json!(true));
+    }
+
+    ExecuteResult::Evm(storage, Vec::new(), validity, ValidityRecords::new())
+  }
+
+  #[test]
+  fn test_record_sync() {
+    let tracker = tracker();
+    assert_eq!(
+      tracker.info("contract").unwrap().status,
+      SyncStatus::Pending
+    );
+    assert!(tracker.execution("contract").is_none());
+    assert!(tracker.info("other").is_none());
+
+    tracker.record(
+      "contract",
+      Ok(evm_execution(&["tx1"], 0)),
+      Some(1000),
+      Some(String::from("000001000,sortkey")),
+      Duration::from_millis(5),
+    );
+
+    let info = tracker.info("contract").unwrap();
+    assert_eq!(info.status, SyncStatus::Synced);
+    assert_eq!(info.height, Some(1000));
+    assert_eq!(info.interactions, 1);
+    assert!(info.synced_at.is_some());
+
+    // A failed sync keeps serving the last state.
+    tracker.record(
+      "contract",
+      Err(ContractError::Network(String::from("timed out"))),
+      Some(1001),
+      None,
+      Duration::from_millis(5),
+    );
+    let info = tracker.info("contract").unwrap();
+    assert_eq!(info.status, SyncStatus::Failed);
+    assert_eq!(info.height, Some(1000));
+    assert_eq!(info.sort_key.as_deref(), Some("000001000,sortkey"));
+    assert!(info.error.unwrap().contains("timed out"));
+    assert!(tracker.execution("contract").is_some());
+    assert_eq!(tracker.infos().len(), 1);
+  }
+
+  #[test]
+  fn test_state_updates() {
+    let tracker = tracker();
+    assert!(tracker.subscribe("other").is_none());
+    let (snapshot, mut updates) = tracker.subscribe("contract").unwrap();
+    assert_eq!(snapshot.state, Value::Null);
+    assert_eq!(snapshot.sort_key, None);
+
+    let sync = |execution: ExecuteResult, sort_key: &str| {
+      tracker.record(
+        "contract",
+        Ok(execution),
+        Some(1000),
+        Some(sort_key.to_owned()),
+        Duration::from_millis(5),
+      )
+    };
+    sync(evm_execution(&["tx1"], 1), "key1");
+    let update = updates.try_recv().unwrap();
+    assert_eq!(update.sort_key.as_deref(), Some("key1"));
+    assert_eq!(update.validity.keys().collect::<Vec<_>>(), vec!["tx1"]);
+    let state = match evm_execution(&[], 1) {
+      ExecuteResult::Evm(storage, ..) => hex::encode(storage.raw()),
+      ExecuteResult::V8(_) => unreachable!(),
+    };
+    assert_eq!(
+      update.patch,
+      vec![PatchOperation::Replace {
+        path: String::new(),
+        value: Value::String(state.to_owned()),
+      }]
+    );
+
+    // Syncs without new interactions are not sent.
+    sync(evm_execution(&["tx1"], 1), "key1");
+    assert!(updates.try_recv().is_err());
+
+    sync(evm_execution(&["tx1", "tx2"], 2), "key2");
+    let update = updates.try_recv().unwrap();
+    assert_eq!(update.validity.keys().collect::<Vec<_>>(), vec!["tx2"]);
+    assert_eq!(update.patch.len(), 1);
+
+    let (snapshot, _) = tracker.subscribe("contract").unwrap();
+    assert_ne!(snapshot.state, Value::String(state));
+    assert_eq!(snapshot.sort_key.as_deref(), Some("key2"));
+  }
+}
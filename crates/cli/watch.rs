@@ -0,0 +1,212 @@
+use crate::dry_run::{evaluate_dry_run, read_dry_run_file};
+use colored::Colorize;
+use deno_core::error::AnyError;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use three_em_executor::json_patch::{diff, PatchOperation};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Change between the outputs of two dry runs.
+#[derive(Debug, PartialEq)]
+pub enum DiffLine {
+  Removed(String, Value),
+  Added(String, Value),
+}
+
+/// Parts of a dry run output compared between runs. Interaction records
+/// are left out since their wall time changes on every run.
+struct Snapshot {
+  state: Value,
+  validity: Value,
+}
+
+impl Snapshot {
+  fn from_output(output: &Value) -> Snapshot {
+    Snapshot {
+      // EVM dry runs output the storage of the contract instead.
+      state: output
+        .get("state")
+        .or_else(|| output.get("storage"))
+        .cloned()
+        .unwrap_or_default(),
+      validity: output.get("validity").cloned().unwrap_or_default(),
+    }
+  }
+}
+
+/// Lines removed from and added to `from` to turn it into `to`.
+pub fn diff_lines(from: &Value, to: &Value) -> Vec<DiffLine> {
+  let mut lines = Vec::new();
+  for operation in diff(from, to) {
+    match operation {
+      PatchOperation::Add { path, value } => {
+        lines.push(DiffLine::Added(path, value));
+      }
+      PatchOperation::Remove { path } => {
+        let old = pointer(from, &path);
+        lines.push(DiffLine::Removed(path, old));
+      }
+      PatchOperation::Replace { path, value } => {
+        let old = pointer(from, &path);
+        lines.push(DiffLine::Removed(path.to_owned(), old));
+        lines.push(DiffLine::Added(path, value));
+      }
+    }
+  }
+  lines
+}
+
+fn pointer(value: &Value, path: &str) -> Value {
+  value.pointer(path).cloned().unwrap_or_default()
+}
+
+fn print_diff(section: &str, from: &Value, to: &Value) {
+  let lines = diff_lines(from, to);
+  if lines.is_empty() {
+    println!("{} {}", section.bold(), "unchanged".dimmed());
+    return;
+  }
+
+  println!("{}", section.bold());
+  for line in lines {
+    match line {
+      DiffLine::Removed(path, value) => {
+        println!("{}", format!("- {} {}", display_path(&path), value).red())
+      }
+      DiffLine::Added(path, value) => {
+        println!("{}", format!("+ {} {}", display_path(&path), value).green())
+      }
+    }
+  }
+}
+
+fn display_path(path: &str) -> &str {
+  if path.is_empty() {
+    "/"
+  } else {
+    path
+  }
+}
+
+/// Modification times of the dry run file and the contract source it points
+/// to. Files that cannot be read have none.
+fn modified(file: &str) -> Vec<Option<SystemTime>> {
+  let mut paths = vec![PathBuf::from(file)];
+  if let Ok(dry) = read_dry_run_file(file) {
+    paths.push(PathBuf::from(dry.contract_source));
+  }
+
+  paths
+    .iter()
+    .map(|path| {
+      std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+    })
+    .collect()
+}
+
+async fn run_once(
+  port: i32,
+  host: &str,
+  protocol: &str,
+  file: &str,
+  trace: &Option<String>,
+  offline: bool,
+  heap_limit: Option<usize>,
+) -> Result<Snapshot, AnyError> {
+  let file = read_dry_run_file(file)?;
+  let (output, mismatches) = evaluate_dry_run(
+    port,
+    host.to_owned(),
+    protocol.to_owned(),
+    &file.run,
+    &file.read_contract_source()?,
+    true,
+    trace.to_owned(),
+    offline,
+    heap_limit,
+  )
+  .await?;
+
+  for mismatch in &mismatches {
+    eprintln!("{}", mismatch.to_string().yellow());
+  }
+
+  Ok(Snapshot::from_output(&output))
+}
+
+/// Evaluates a dry run file every time it or its contract source changes,
+/// printing what changed in the state and validity since the previous run.
+pub async fn dry_run_watch(
+  port: i32,
+  host: String,
+  protocol: String,
+  file: String,
+  trace: Option<String>,
+  offline: bool,
+  heap_limit: Option<usize>,
+) -> Result<(), AnyError> {
+  let mut previous: Option<Snapshot> = None;
+  let mut last_modified = modified(&file);
+
+  loop {
+    println!("{}", format!("Evaluating {}", file).cyan());
+
+    // Errors are reported and the next change is awaited, so a typo in the
+    // contract does not end the session.
+    match run_once(port, &host, &protocol, &file, &trace, offline, heap_limit)
+      .await
+    {
+      Ok(snapshot) => {
+        match &previous {
+          Some(previous) => {
+            print_diff("state", &previous.state, &snapshot.state);
+            print_diff("validity", &previous.validity, &snapshot.validity);
+          }
+          None => println!(
+            "{}",
+            serde_json::to_string_pretty(&snapshot.state).unwrap()
+          ),
+        }
+        previous = Some(snapshot);
+      }
+      Err(err) => eprintln!("{}", err.to_string().red()),
+    }
+
+    println!("{}", "Watching for changes...".dimmed());
+    loop {
+      tokio::time::sleep(POLL_INTERVAL).await;
+      let current = modified(&file);
+      if current != last_modified {
+        last_modified = current;
+        break;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::watch::{diff_lines, DiffLine};
+  use serde_json::json;
+
+  #[test]
+  fn test_diff_lines() {
+    let from = json!({ "count": 2, "users": ["Andres Pirela", "Divy"] });
+    let to = json!({ "count": 1, "users": ["Andres Pirela"], "open": true });
+
+    assert_eq!(
+      diff_lines(&from, &to),
+      vec![
+        DiffLine::Removed(String::from("/count"), json!(2)),
+        DiffLine::Added(String::from("/count"), json!(1)),
+        DiffLine::Removed(String::from("/users/1"), json!("Divy")),
+        DiffLine::Added(String::from("/open"), json!(true)),
+      ]
+    );
+    assert!(diff_lines(&from, &from).is_empty());
+  }
+}
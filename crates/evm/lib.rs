@@ -361,6 +361,11 @@ impl<'a> Machine<'a> {
     }
   }
 
+  /// Total gas used by the executed instructions.
+  pub fn gas_used(&self) -> U256 {
+    self.gas_used
+  }
+
   pub fn set_storage(&mut self, storage: Storage) {
     self.storage = storage;
   }
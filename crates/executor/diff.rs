@@ -0,0 +1,182 @@
+use crate::executor::{raw_execute_contract, ExecuteResult};
+use crate::json_patch::{apply, diff, PatchOperation};
+use crate::sort_interactions;
+use deno_core::serde_json;
+use deno_core::serde_json::Value;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use three_em_arweave::arweave::Arweave;
+use three_em_arweave::errors::ContractError;
+use three_em_arweave::gql_result::GQLEdgeInterface;
+use three_em_arweave::miscellaneous::{get_sort_key, ValidityRecord};
+
+/// Interaction evaluated between the two heights of a diff.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffInteraction {
+  pub id: String,
+  pub height: usize,
+  pub sort_key: String,
+  /// Entry of the plain validity table.
+  pub valid: Value,
+  pub record: Option<ValidityRecord>,
+}
+
+/// Change of a contract's state between two heights.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StateDiff {
+  pub from_height: usize,
+  pub to_height: usize,
+  /// RFC 6902 JSON Patch from the state at `from_height` to the state at
+  /// `to_height`.
+  pub patch: Vec<PatchOperation>,
+  pub interactions: Vec<DiffInteraction>,
+}
+
+/// Evaluates a contract at two heights and describes how its state changed
+/// between them. Contract sources and interactions come from the cache when
+/// `cache` is set.
+pub async fn diff_contract(
+  contract_id: String,
+  from_height: usize,
+  to_height: usize,
+  cache: bool,
+  show_errors: bool,
+  arweave: &Arweave,
+) -> Result<StateDiff, ContractError> {
+  if from_height > to_height {
+    return Err(ContractError::HeightRange {
+      from: from_height,
+      to: to_height,
+    });
+  }
+
+  let (loaded_contract, interactions) = tokio::join!(
+    arweave.load_contract(
+      contract_id.to_owned(),
+      None,
+      None,
+      None,
+      cache,
+      false,
+      false
+    ),
+    arweave.get_interactions(contract_id.to_owned(), Some(to_height), cache)
+  );
+  let loaded_contract = loaded_contract?;
+  let (mut interactions, _, _) = interactions?;
+
+  // Cached interactions may go past the requested height.
+  interactions.retain(|interaction| interaction.node.block.height <= to_height);
+  sort_interactions(&mut interactions);
+
+  let split = interactions
+    .iter()
+    .position(|interaction| interaction.node.block.height > from_height)
+    .unwrap_or(interactions.len());
+  let between: Vec<GQLEdgeInterface> = interactions[split..].to_vec();
+  let before: HashSet<String> = interactions[..split]
+    .iter()
+    .map(|interaction| interaction.node.id.to_owned())
+    .collect();
+
+  let execution_error = |reason: String| ContractError::Execution {
+    contract: contract_id.to_owned(),
+    reason,
+  };
+  let init_state: Value = serde_json::from_str(&loaded_contract.init_state)
+    .map_err(|err| ContractError::InitState {
+      contract: contract_id.to_owned(),
+      reason: err.to_string(),
+    })?;
+
+  // The persisted state belongs to the latest height, so the contract is
+  // evaluated from its init state. The trace of the evaluation gives the
+  // state at `from_height` along the way.
+  let to = match raw_execute_contract(
+    contract_id.to_owned(),
+    loaded_contract,
+    interactions,
+    None,
+    true,
+    show_errors,
+    |_, _| unreachable!("interactions are always processed"),
+    &arweave.to_owned().with_tracing(true),
+    HashMap::new(),
+    None,
+  )
+  .await?
+  {
+    ExecuteResult::V8(result) => result,
+    ExecuteResult::Evm(..) => {
+      return Err(execution_error(String::from(
+        "EVM contracts cannot be diffed",
+      )))
+    }
+  };
+
+  let mut from_state = init_state;
+  for entry in to.trace.iter().filter(|entry| before.contains(&entry.id)) {
+    apply(&mut from_state, &entry.patch)
+      .map_err(|err| execution_error(err.to_string()))?;
+  }
+
+  let interactions = between
+    .into_iter()
+    .map(|interaction| {
+      let tx = interaction.node;
+      DiffInteraction {
+        sort_key: get_sort_key(&tx.block.height, &tx.block.id, &tx.id),
+        height: tx.block.height,
+        valid: to.validity.get(&tx.id).cloned().unwrap_or(Value::Null),
+        record: to.records.get(&tx.id).cloned(),
+        id: tx.id,
+      }
+    })
+    .collect();
+
+  Ok(StateDiff {
+    from_height,
+    to_height,
+    patch: diff(&from_state, &to.state),
+    interactions,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::diff::diff_contract;
+  use three_em_arweave::arweave::Arweave;
+  use three_em_arweave::cache::ArweaveCache;
+  use three_em_arweave::cache::CacheExt;
+  use three_em_arweave::errors::ContractError;
+
+  #[tokio::test]
+  async fn test_diff_height_range() {
+    let arweave = Arweave::new(
+      443,
+      "arweave.net".to_string(),
+      String::from("https"),
+      ArweaveCache::new(),
+    );
+
+    let result = diff_contract(
+      String::from("t9T7DIOGxx4VWXoCEeYYarFYeERTpWIC1V3y-BPZgKE"),
+      749180,
+      749000,
+      false,
+      false,
+      &arweave,
+    )
+    .await;
+
+    assert!(matches!(
+      result,
+      Err(ContractError::HeightRange {
+        from: 749180,
+        to: 749000
+      })
+    ));
+  }
+}
@@ -1,4 +1,9 @@
-use crate::{get_input_from_interaction, nop_cost_fn};
+use crate::requirements::InteractionRequirements;
+use crate::runtime::{
+  get_evolve_source, rebuild_exm_context, CallOutcome, ContractRuntime,
+};
+use crate::trace::{Trace, TraceEntry};
+use crate::{get_input_for_contract, nop_cost_fn};
 use deno_core::error::AnyError;
 use deno_core::serde_json;
 use deno_core::serde_json::Value;
@@ -6,26 +11,29 @@ use deno_core::OpState;
 use deno_ops::op;
 use indexmap::map::IndexMap;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
+use std::time::Instant;
 use three_em_arweave::arweave::get_cache;
 use three_em_arweave::arweave::LoadedContract;
-use three_em_arweave::arweave::{Arweave, ArweaveProtocol};
+use three_em_arweave::arweave::{
+  Arweave, ArweaveProtocol, ManualLoadedContract,
+};
 use three_em_arweave::cache::ArweaveCache;
 use three_em_arweave::cache::CacheExt;
-use three_em_arweave::cache::StateResult;
+use three_em_arweave::cache::{RecordedRequests, StateResult};
+use three_em_arweave::errors::ContractError;
 use three_em_arweave::gql_result::{
   GQLAmountInterface, GQLEdgeInterface, GQLNodeInterface,
 };
-use three_em_arweave::miscellaneous::ContractType;
-use three_em_evm::{ExecutionState, Machine, Storage};
+pub use three_em_arweave::miscellaneous::Evolution;
+use three_em_arweave::miscellaneous::{get_sort_key, ContractType};
+use three_em_arweave::miscellaneous::{ValidityRecord, ValidityRecords};
+use three_em_evm::{BlockInfo, ExecutionState, Machine, Storage};
 use three_em_exm_base_ops::ExmContext;
-use three_em_js::CallResult;
-use three_em_js::Runtime;
 use three_em_smartweave::{
   InteractionBlock, InteractionContext, InteractionTx,
 };
-use three_em_wasm::WasmRuntime;
 
 /*
  * Questions:
@@ -46,16 +54,83 @@ pub struct V8Result {
   pub context: ExmContext,
   pub updated: bool,
   pub errors: HashMap<String, String>,
+  pub evolutions: Vec<Evolution>,
+  pub records: ValidityRecords,
+  /// Evaluated interactions, when tracing is enabled.
+  pub trace: Vec<TraceEntry>,
 }
 
 #[derive(Clone)]
 pub enum ExecuteResult {
   V8(V8Result),
-  Evm(Storage, Vec<u8>, ValidityTable),
+  Evm(Storage, Vec<u8>, ValidityTable, ValidityRecords),
 }
 
 pub type OnCached = dyn Fn() -> ExecuteResult;
 
+// Outcome of the interactions evaluated by `raw_execute_contract`.
+struct Outcomes {
+  validity: ValidityTable,
+  records: ValidityRecords,
+  errors: ExecErrors,
+  trace: Option<Trace>,
+  show_errors: bool,
+}
+
+impl Outcomes {
+  // Records the outcome of an interaction, both in the plain validity table
+  // and as a typed record. `state` is the state it left behind, which the
+  // trace needs, or `None` if it did not change the state.
+  fn record(
+    &mut self,
+    tx: &GQLNodeInterface,
+    record: ValidityRecord,
+    started: Instant,
+    state: Option<Value>,
+  ) {
+    let record = ValidityRecord {
+      wall_time_us: started.elapsed().as_micros() as u64,
+      ..record
+    };
+
+    if let Some(error) = &record.error {
+      if self.show_errors {
+        println!("{}", error.message);
+      }
+      self
+        .errors
+        .insert(tx.id.to_owned(), error.message.to_owned());
+    }
+    if let Some(trace) = &mut self.trace {
+      trace.push(tx, &record, state);
+    }
+
+    self
+      .validity
+      .insert(tx.id.to_owned(), record.to_legacy(self.show_errors));
+    self.records.insert(tx.id.to_owned(), record);
+  }
+
+  fn is_tracing(&self) -> bool {
+    self.trace.is_some()
+  }
+}
+
+/// Adds the validity of the evaluated interactions to an output object.
+/// `validity` keeps the plain table, `interactions` holds the typed record
+/// of each interaction.
+pub fn with_validity(
+  mut value: Value,
+  validity: &ValidityTable,
+  records: &ValidityRecords,
+) -> Value {
+  if let Some(object) = value.as_object_mut() {
+    object.insert(String::from("validity"), serde_json::json!(validity));
+    object.insert(String::from("interactions"), serde_json::json!(records));
+  }
+  value
+}
+
 pub fn get_execution_context(
   maybe_context: Result<ExmContext, AnyError>,
 ) -> ExmContext {
@@ -65,7 +140,7 @@ pub fn get_execution_context(
   } else {
     ExmContext {
       requests: HashMap::new(),
-      kv: HashMap::new(),
+      kv: BTreeMap::new(),
       //data: Data::new(),
     }
   }
@@ -79,25 +154,31 @@ pub fn process_execution(
   match execute_result {
     ExecuteResult::V8(result) => {
       if show_validity {
-        serde_json::json!({
-            "state": result.state,
-            "validity": result.validity,
-            "exm": result.context
-        })
+        with_validity(
+          serde_json::json!({
+              "state": result.state,
+              "exm": result.context
+          }),
+          &result.validity,
+          &result.records,
+        )
       } else {
         result.state
       }
     }
-    ExecuteResult::Evm(store, result, validity_table) => {
+    ExecuteResult::Evm(store, result, validity_table, records) => {
       let store = hex::encode(store.raw());
       let result = hex::encode(result);
 
       if show_validity {
-        serde_json::json!({
-            "result": result,
-            "store": store,
-            "validity": validity_table
-        })
+        with_validity(
+          serde_json::json!({
+              "result": result,
+              "store": store,
+          }),
+          &validity_table,
+          &records,
+        )
       } else {
         serde_json::json!({
             "result": result,
@@ -116,6 +197,21 @@ pub async fn op_smartweave_read_contract(
 ) -> Result<Value, AnyError> {
   //Reads the state in the contract, not write to it
   let op_state = state.borrow();
+  if let Some(fixtures) = op_state.try_borrow::<three_em_smartweave::Fixtures>()
+  {
+    let state = fixtures.contracts.get(&contract_id).ok_or_else(|| {
+      three_em_smartweave::missing_fixture(format!("contract {}", contract_id))
+    })?;
+    return Ok(if show_validity.unwrap_or(false) {
+      with_validity(
+        serde_json::json!({ "state": state }),
+        &IndexMap::new(),
+        &IndexMap::new(),
+      )
+    } else {
+      state.to_owned()
+    });
+  }
   let info = op_state.borrow::<three_em_smartweave::ArweaveInfo>(); //Borrow data in ArweaveInfo type
   let cl = Arweave::new(
     info.port,
@@ -148,7 +244,7 @@ pub fn generate_interaction_context(
           ar: Some(String::new()),
         })
         .winston
-        .unwrap(),
+        .unwrap_or_default(),
       reward: tx
         .fee
         .to_owned()
@@ -157,7 +253,7 @@ pub fn generate_interaction_context(
           ar: Some(String::new()),
         })
         .winston
-        .unwrap(),
+        .unwrap_or_default(),
       tags: tx.tags.to_owned(),
     },
     block: InteractionBlock {
@@ -170,22 +266,27 @@ pub fn generate_interaction_context(
 
 #[allow(clippy::too_many_arguments)]
 pub async fn raw_execute_contract<
-  CachedCallBack: FnOnce(ValidityTable, CachedState, ExecErrors) -> ExecuteResult,
+  CachedCallBack: FnOnce(StateResult, ExecErrors) -> ExecuteResult,
 >(
   contract_id: String,
   loaded_contract: LoadedContract,
   interactions: Vec<GQLEdgeInterface>,
-  mut validity: IndexMap<String, Value>,
-  cache_state: Option<Value>,
+  cached: Option<StateResult>,
   needs_processing: bool,
   show_errors: bool,
   on_cached: CachedCallBack,
   shared_client: &Arweave,
   settings: HashMap<String, deno_core::serde_json::Value>,
   maybe_exm_context: Option<deno_core::serde_json::Value>,
-) -> ExecuteResult {
+) -> Result<ExecuteResult, ContractError> {
+  let mut settings = settings;
+  if let Some(heap_limit) = shared_client.heap_limit {
+    settings
+      .entry(String::from("HEAP_LIMIT"))
+      .or_insert_with(|| Value::String(heap_limit.to_string()));
+  }
   let transaction = (&loaded_contract.contract_transaction).to_owned();
-  let cache = cache_state.is_some();
+  let cache = cached.is_some();
   let arweave_info = (
     shared_client.port.to_owned(),
     shared_client.host.to_owned(),
@@ -195,255 +296,394 @@ pub async fn raw_execute_contract<
     },
   );
   let mut is_state_updated = false;
-  let mut errors: HashMap<String, String> = HashMap::new();
+  let mut outcomes = Outcomes {
+    validity: ValidityTable::new(),
+    records: ValidityRecords::new(),
+    errors: HashMap::new(),
+    trace: None,
+    show_errors,
+  };
+  // Requirements the contract sets incorrectly reject every interaction.
+  let requirements = if shared_client.enforce_requirements {
+    InteractionRequirements::from_contract(&loaded_contract)
+  } else {
+    Ok(InteractionRequirements::default())
+  };
+  let check_requirements = |tx: &GQLNodeInterface| match &requirements {
+    Ok(requirements) => requirements.check(tx),
+    Err(err) => Err(err.to_owned()),
+  };
 
   match loaded_contract.contract_type {
-    ContractType::JAVASCRIPT => {
+    ContractType::JAVASCRIPT | ContractType::WASM => {
       if needs_processing {
-        let state: Value = cache_state.unwrap_or_else(|| {
-          deno_core::serde_json::from_str(&loaded_contract.init_state).unwrap()
-        });
+        let is_cached = cached.is_some();
+        // Requests recorded by earlier evaluations are replayed. Evolutions
+        // and records before the cached state were stored along with it.
+        let (state, mut requests, mut evolutions): (
+          Value,
+          RecordedRequests,
+          Vec<Evolution>,
+        ) = match cached {
+          Some(cached) => {
+            outcomes.validity = cached.validity;
+            outcomes.records = cached.records;
+            (cached.state, cached.requests, cached.evolutions)
+          }
+          None => (
+            deno_core::serde_json::from_str(&loaded_contract.init_state)
+              .map_err(|err| ContractError::InitState {
+                contract: contract_id.to_owned(),
+                reason: err.to_string(),
+              })?,
+            RecordedRequests::new(),
+            Vec::new(),
+          ),
+        };
+
+        let mut source_tx = loaded_contract.contract_src_tx_id;
+        let mut contract_src = loaded_contract.contract_src;
+        let mut contract_type = loaded_contract.contract_type;
+        if shared_client.trace {
+          outcomes.trace = Some(Trace::new(&contract_id, state.clone()));
+        }
+
+        // A cached state may already point to an evolved source.
+        if is_cached {
+          if let Some(evolve) =
+            get_evolve_source(&state).filter(|evolve| *evolve != source_tx)
+          {
+            let source = shared_client
+              .load_contract_source(&evolve)
+              .await
+              .map_err(|err| ContractError::Evolve {
+                contract: contract_id.to_owned(),
+                source_tx: evolve.to_owned(),
+                reason: err.to_string(),
+              })?;
+            contract_src = source.contract_src;
+            contract_type = source.contract_type;
+            source_tx = evolve;
+          }
+        }
 
-        let mut rt = Runtime::new(
-          &(String::from_utf8(loaded_contract.contract_src).unwrap()),
+        let mut rt = ContractRuntime::new(
+          &contract_id,
+          &contract_type,
+          &contract_src,
           state,
           arweave_info.to_owned(),
-          op_smartweave_read_contract::decl(),
           settings.clone(),
           maybe_exm_context.clone(),
         )
-        .await
-        .unwrap();
+        .await?;
 
         let mut latest_result: Option<Value> = None;
 
-        // let mut i = 0;
         for interaction in interactions {
           let tx = interaction.node;
-          let input = get_input_from_interaction(&tx);
+          let started = Instant::now();
+
+          if shared_client.verify_signatures {
+            if let Err(err) = shared_client.verify_interaction(&tx).await {
+              outcomes.record(
+                &tx,
+                ValidityRecord::invalid("invalid_signature", err.to_string()),
+                started,
+                None,
+              );
+              continue;
+            }
+          }
 
-          // TODO: has_multiple_interactions
-          // https://github.com/ArweaveTeam/SmartWeave/blob/4d09c66d832091805f583ba73e8da96cde2c0190/src/contract-read.ts#L68
-          let js_input_maybe: serde_json::Result<Value> =
-            deno_core::serde_json::from_str(input);
+          if let Err(err) = check_requirements(&tx) {
+            outcomes.record(&tx, err.to_record(), started, None);
+            continue;
+          }
 
-          if let Ok(js_input) = js_input_maybe {
-            let call_input = serde_json::json!({
-              "input": js_input,
-              "caller": tx.owner.address
-            });
+          let js_input: Value = match get_input_for_contract(&tx, &contract_id)
+            .and_then(|input| serde_json::from_str(input).ok())
+          {
+            Some(js_input) => js_input,
+            None => {
+              outcomes.record(
+                &tx,
+                ValidityRecord::invalid(
+                  "invalid_input",
+                  String::from("Missing or malformed Input tag"),
+                ),
+                started,
+                None,
+              );
+              continue;
+            }
+          };
 
-            let interaction_context = generate_interaction_context(&tx);
+          let call_input = serde_json::json!({
+            "input": js_input,
+            "caller": tx.owner.address
+          });
 
-            let valid =
-              match rt.call(call_input, Some(interaction_context)).await {
-                Ok(None) => {
-                  latest_result = None;
-                  serde_json::Value::Bool(true)
-                }
-                Ok(Some(CallResult::Evolve(evolve))) => {
-                  let contract = shared_client
-                    .load_contract(
-                      contract_id.clone(),
-                      Some(evolve),
-                      None,
-                      None,
-                      true,
-                      false,
-                      false,
-                    )
-                    .await
-                    .unwrap();
-
-                  let state: Value = rt.get_contract_state().unwrap();
-                  rt = Runtime::new(
-                    &(String::from_utf8_lossy(&contract.contract_src)),
-                    state,
-                    arweave_info.to_owned(),
-                    op_smartweave_read_contract::decl(),
-                    settings.clone(),
-                    maybe_exm_context.clone(),
-                  )
-                  .await
-                  .unwrap();
+          let interaction_context = generate_interaction_context(&tx);
+          let mut failure: Option<ValidityRecord> = None;
+          let mut state_changed = false;
+          let cost_before = rt.cost();
 
-                  latest_result = None;
+          rt.begin_interaction(
+            requests.get(&tx.id).cloned().unwrap_or_default(),
+          )
+          .map_err(|err| ContractError::Execution {
+            contract: contract_id.to_owned(),
+            reason: err.to_string(),
+          })?;
+          let call_result = rt.call(call_input, interaction_context).await;
+          let request_store = rt.end_interaction();
+
+          if let Some(reason) = request_store.divergence {
+            return Err(ContractError::RequestDivergence {
+              contract: contract_id.to_owned(),
+              interaction: tx.id,
+              reason,
+            });
+          }
+          if !request_store.responses.is_empty() {
+            requests.insert(tx.id.to_owned(), request_store.responses);
+          }
 
-                  serde_json::Value::Bool(true)
-                }
-                Ok(Some(CallResult::Result(result_value, state_updated))) => {
-                  let result_to_value = rt.to_value::<Value>(&result_value);
-                  if result_to_value.is_ok() {
-                    latest_result = Some(result_to_value.unwrap());
-                  }
+          match call_result {
+            Ok(CallOutcome::Applied {
+              result,
+              state_updated,
+            }) => {
+              latest_result = result;
+              state_changed = state_updated;
+
+              if state_updated && !is_state_updated {
+                is_state_updated = state_updated;
+              }
+            }
+            Ok(CallOutcome::Evolve {
+              source: evolve,
+              result,
+            }) => {
+              let evolved = if evolve != source_tx {
+                evolve_runtime(
+                  &mut rt,
+                  &contract_id,
+                  &evolve,
+                  shared_client,
+                  &arweave_info,
+                  &settings,
+                  maybe_exm_context.clone(),
+                )
+                .await
+                .map(Some)
+              } else {
+                Ok(None)
+              };
 
-                  if state_updated && !is_state_updated {
-                    is_state_updated = state_updated;
+              match evolved {
+                Ok(evolved) => {
+                  if let Some((evolved, source)) = evolved {
+                    rt = evolved;
+                    evolutions.push(Evolution {
+                      sort_key: get_sort_key(
+                        &tx.block.height,
+                        &tx.block.id,
+                        &tx.id,
+                      ),
+                      interaction: tx.id.to_owned(),
+                      source_tx: evolve.to_owned(),
+                      contract_type: source.contract_type.clone(),
+                    });
+                    contract_src = source.contract_src;
+                    contract_type = source.contract_type;
+                    source_tx = evolve;
                   }
 
-                  serde_json::Value::Bool(true)
+                  latest_result = result;
+                  state_changed = true;
+                  is_state_updated = true;
                 }
                 Err(err) => {
-                  let err_str = err.to_string();
-
-                  errors.insert(tx.id.clone(), err_str.clone());
-
                   latest_result = None;
 
-                  if show_errors {
-                    println!("{}", err);
-                  }
+                  // The interaction is invalid when the source it evolves
+                  // to cannot be loaded, so the previous source keeps
+                  // evaluating from the state before it.
+                  rt = rollback_runtime(
+                    &mut rt,
+                    &contract_id,
+                    &contract_type,
+                    &contract_src,
+                    &arweave_info,
+                    &settings,
+                    maybe_exm_context.clone(),
+                  )
+                  .await?;
 
-                  if show_errors {
-                    serde_json::Value::String(err_str)
-                  } else {
-                    serde_json::Value::Bool(false)
-                  }
+                  failure =
+                    Some(ValidityRecord::invalid(err.kind(), err.to_string()));
                 }
-              };
-            validity.insert(tx.id, valid);
-          } else {
-            validity.insert(tx.id, deno_core::serde_json::Value::Bool(false));
+              }
+            }
+            Err(err) => {
+              latest_result = None;
+
+              rt = rollback_runtime(
+                &mut rt,
+                &contract_id,
+                &contract_type,
+                &contract_src,
+                &arweave_info,
+                &settings,
+                maybe_exm_context.clone(),
+              )
+              .await?;
+
+              failure =
+                Some(ValidityRecord::invalid("execution", err.to_string()));
+            }
           }
+
+          // A runtime rebuilt after an evolution or a failure restarts its
+          // metering.
+          let gas_used = match (cost_before, rt.cost()) {
+            (Some(before), Some(after)) if after >= before => {
+              Some(after - before)
+            }
+            (_, cost) => cost,
+          };
+          let record = failure.unwrap_or_else(|| ValidityRecord {
+            valid: true,
+            state_changed,
+            result: latest_result.clone(),
+            ..Default::default()
+          });
+          let state = if outcomes.is_tracing() && state_changed {
+            rt.get_state().ok()
+          } else {
+            None
+          };
+          outcomes.record(
+            &tx,
+            ValidityRecord { gas_used, ..record },
+            started,
+            state,
+          );
         }
 
-        let state_val: Value = rt.get_contract_state().unwrap();
-        let exm_context: ExmContext =
-          get_execution_context(rt.get_exm_context::<ExmContext>());
+        let state_val: Value =
+          rt.get_state().map_err(|err| ContractError::Execution {
+            contract: contract_id.to_owned(),
+            reason: err.to_string(),
+          })?;
+        let exm_context: ExmContext = rt.get_exm_context();
 
         if cache {
           get_cache().lock().unwrap().cache_states(
             contract_id,
             StateResult {
               state: state_val.clone(),
-              validity: validity.clone(),
+              validity: outcomes.validity.clone(),
+              evolutions: evolutions.clone(),
+              requests,
+              records: outcomes.records.clone(),
             },
           );
         }
 
-        ExecuteResult::V8(V8Result {
+        Ok(ExecuteResult::V8(V8Result {
           state: state_val,
           result: latest_result,
-          validity,
+          validity: outcomes.validity,
           context: exm_context,
           updated: is_state_updated,
-          errors,
-        })
-      } else {
-        on_cached(validity, cache_state, errors)
-      }
-    }
-    ContractType::WASM => {
-      if needs_processing {
-        let wasm = loaded_contract.contract_src.as_slice();
-
-        let init_state_wasm = if cache_state.is_some() {
-          let cache_state_unwrapped = cache_state.unwrap();
-          let state_str = cache_state_unwrapped.to_string();
-          state_str.as_bytes().to_vec()
-        } else {
-          loaded_contract.init_state.as_bytes().to_vec()
-        };
-
-        let mut state = init_state_wasm;
-        let mut rt = WasmRuntime::new(wasm).unwrap();
-
-        for interaction in interactions {
-          let tx = interaction.node;
-
-          let input = get_input_from_interaction(&tx);
-          let maybe_wasm_input: serde_json::Result<Value> =
-            deno_core::serde_json::from_str(input);
-          if let Ok(wasm_input) = maybe_wasm_input {
-            let call_input = serde_json::json!({
-              "input": wasm_input,
-              "caller": tx.owner.address,
-            });
-            let interaction_context = generate_interaction_context(&tx);
-
-            let mut input = deno_core::serde_json::to_vec(&call_input).unwrap();
-            let exec = rt.call(&mut state, &mut input, interaction_context);
-            let valid_with_result = match exec {
-              Ok(result) => (serde_json::Value::Bool(true), Some(result)),
-              Err(err) => {
-                if show_errors {
-                  println!("{}", err);
-                }
-
-                if show_errors {
-                  (serde_json::Value::String(err.to_string()), None)
-                } else {
-                  (serde_json::Value::Bool(false), None)
-                }
-              }
-            };
-            let valid = valid_with_result.0;
-
-            if valid.is_boolean() && valid.as_bool().unwrap() {
-              state = valid_with_result.1.unwrap();
-            }
-            validity.insert(tx.id, valid);
-          } else {
-            validity.insert(tx.id, deno_core::serde_json::Value::Bool(false));
-          }
-        }
-
-        let state: Value = deno_core::serde_json::from_slice(&state).unwrap();
-        /// TODO: WASM Context
-        // let exm_context =
-        //   get_execution_context(rt.get_exm_context::<ExmContext>());
-        if cache {
-          get_cache().lock().unwrap().cache_states(
-            contract_id,
-            StateResult {
-              state: state.clone(),
-              validity: validity.clone(),
-            },
-          );
-        }
-
-        ExecuteResult::V8(V8Result {
-          state,
-          validity,
-          context: Default::default(),
-          result: None,
-          updated: false,
-          errors: errors,
-        })
+          errors: outcomes.errors,
+          evolutions,
+          records: outcomes.records,
+          trace: outcomes.trace.map(Trace::into_entries).unwrap_or_default(),
+        }))
       } else {
-        on_cached(validity, cache_state, errors)
+        let cached =
+          cached.expect("unprocessed interactions need a cached state");
+        Ok(on_cached(cached, outcomes.errors))
       }
     }
     ContractType::EVM => {
       // Contract source bytes.
       let bytecode = hex::decode(loaded_contract.contract_src.as_slice())
-        .expect("Failed to decode contract bytecode");
-      let store = hex::decode(loaded_contract.init_state.as_bytes())
-        .expect("Failed to decode account state");
+        .map_err(|err| ContractError::SourceEncoding {
+          contract: contract_id.to_owned(),
+          reason: err.to_string(),
+        })?;
+      let store =
+        hex::decode(loaded_contract.init_state.as_bytes()).map_err(|err| {
+          ContractError::InitState {
+            contract: contract_id.to_owned(),
+            reason: err.to_string(),
+          }
+        })?;
 
       let mut account_store = Storage::from_raw(&store);
       let mut result = vec![];
       for interaction in interactions {
         let tx = interaction.node;
-        let block_info =
-          shared_client.get_transaction_block(&tx.id).await.unwrap();
+        let started = Instant::now();
+
+        if shared_client.verify_signatures {
+          if let Err(err) = shared_client.verify_interaction(&tx).await {
+            outcomes.record(
+              &tx,
+              ValidityRecord::invalid("invalid_signature", err.to_string()),
+              started,
+              None,
+            );
+            continue;
+          }
+        }
+
+        if let Err(err) = check_requirements(&tx) {
+          outcomes.record(&tx, err.to_record(), started, None);
+          continue;
+        }
+
+        let block_info = shared_client.get_transaction_block(&tx.id).await?;
 
         let block_info = three_em_evm::BlockInfo {
           timestamp: three_em_evm::U256::from(block_info.timestamp),
           difficulty: three_em_evm::U256::from_str_radix(&block_info.diff, 10)
-            .unwrap(),
+            .map_err(|_| {
+              ContractError::Network(format!(
+                "Invalid difficulty in block of transaction {}",
+                tx.id
+              ))
+            })?,
           block_hash: three_em_evm::U256::from(
             block_info.indep_hash.as_bytes(),
           ),
           number: three_em_evm::U256::from(block_info.height),
         };
 
-        let input = get_input_from_interaction(&tx);
-        let call_data = hex::decode(input).expect("Failed to decode input");
+        let input =
+          get_input_for_contract(&tx, &contract_id).unwrap_or_default();
+        let call_data = match hex::decode(input) {
+          Ok(call_data) => call_data,
+          Err(err) => {
+            outcomes.record(
+              &tx,
+              ValidityRecord::invalid(
+                "invalid_call_data",
+                format!("Invalid call data: {}", err),
+              ),
+              started,
+              None,
+            );
+            continue;
+          }
+        };
 
         let mut machine = Machine::new_with_data(nop_cost_fn, call_data);
-        machine.set_storage(account_store.clone());
-
         machine.set_fetcher(Box::new(|address: &three_em_evm::U256| {
           let mut id = [0u8; 32];
           address.to_big_endian(&mut id);
@@ -452,35 +692,162 @@ pub async fn raw_execute_contract<
             shared_client
               .load_contract(id, None, None, None, cache, false, false),
           )
-          .expect("evm call: Failed to load contract");
+          .ok()?;
 
-          let bytecode = hex::decode(contract.contract_src.as_slice())
-            .expect("Failed to decode contract bytecode");
-          let store = hex::decode(contract.init_state.as_bytes())
-            .expect("Failed to decode account state");
+          let bytecode = hex::decode(contract.contract_src.as_slice()).ok()?;
+          let store = hex::decode(contract.init_state.as_bytes()).ok()?;
 
           let store = Storage::from_raw(&store);
 
           Some(three_em_evm::ContractInfo { store, bytecode })
         }));
 
-        match machine.execute(&bytecode, block_info) {
-          ExecutionState::Abort(_) | ExecutionState::Revert => {
-            validity.insert(tx.id, serde_json::Value::Bool(false));
-          }
-          ExecutionState::Ok => {
-            account_store = machine.storage;
-            result = machine.result;
-            validity.insert(tx.id, serde_json::Value::Bool(true));
-          }
-        }
+        let record = execute_evm_call(
+          machine,
+          &bytecode,
+          block_info,
+          &mut account_store,
+          &mut result,
+        );
+        outcomes.record(&tx, record, started, None);
       }
 
-      ExecuteResult::Evm(account_store, result, validity)
+      Ok(ExecuteResult::Evm(
+        account_store,
+        result,
+        outcomes.validity,
+        outcomes.records,
+      ))
     }
   }
 }
 
+/// Runtime of the source `rt` evolves to, starting from the state and KV
+/// entries it holds, along with the type of the source.
+pub async fn evolve_runtime(
+  rt: &mut ContractRuntime,
+  contract_id: &str,
+  source_tx: &str,
+  shared_client: &Arweave,
+  arweave_info: &(i32, String, String),
+  settings: &HashMap<String, Value>,
+  maybe_exm_context: Option<Value>,
+) -> Result<(ContractRuntime, ManualLoadedContract), ContractError> {
+  let evolve_error = |reason: String| ContractError::Evolve {
+    contract: contract_id.to_owned(),
+    source_tx: source_tx.to_owned(),
+    reason,
+  };
+
+  if settings.contains_key("FIXTURES") {
+    return Err(evolve_error(String::from(
+      "evolved sources cannot be loaded offline",
+    )));
+  }
+  let source = shared_client
+    .load_contract_source(source_tx)
+    .await
+    .map_err(|err| evolve_error(err.to_string()))?;
+  let state = rt
+    .get_state()
+    .map_err(|err| evolve_error(err.to_string()))?;
+  let exm_context =
+    rebuild_exm_context(maybe_exm_context, rt.get_exm_context())
+      .map_err(|err| evolve_error(err.to_string()))?;
+
+  // The evolved source may run in a different runtime.
+  let evolved = ContractRuntime::new(
+    contract_id,
+    &source.contract_type,
+    &source.contract_src,
+    state,
+    arweave_info.to_owned(),
+    settings.to_owned(),
+    exm_context,
+  )
+  .await
+  .map_err(|err| evolve_error(err.to_string()))?;
+
+  Ok((evolved, source))
+}
+
+/// Runtime evaluating from the state before a failed interaction. The
+/// runtime is rebuilt with the KV entries written before the interaction,
+/// which also drops whatever it changed in the globals of the contract.
+#[allow(clippy::too_many_arguments)]
+pub async fn rollback_runtime(
+  rt: &mut ContractRuntime,
+  contract_id: &str,
+  contract_type: &ContractType,
+  contract_src: &[u8],
+  arweave_info: &(i32, String, String),
+  settings: &HashMap<String, Value>,
+  maybe_exm_context: Option<Value>,
+) -> Result<ContractRuntime, ContractError> {
+  let execution_error = |err: AnyError| ContractError::Execution {
+    contract: contract_id.to_owned(),
+    reason: err.to_string(),
+  };
+
+  rt.rollback_interaction().map_err(execution_error)?;
+  let state = rt.get_state().map_err(execution_error)?;
+  let exm_context =
+    rebuild_exm_context(maybe_exm_context, rt.get_exm_context())
+      .map_err(execution_error)?;
+
+  ContractRuntime::new(
+    contract_id,
+    contract_type,
+    contract_src,
+    state,
+    arweave_info.to_owned(),
+    settings.to_owned(),
+    exm_context,
+  )
+  .await
+}
+
+/// Runs a call of an EVM contract against `storage`. The storage and
+/// return data of the call replace `storage` and `result` only when it
+/// succeeds.
+pub fn execute_evm_call(
+  mut machine: Machine,
+  bytecode: &[u8],
+  block_info: BlockInfo,
+  storage: &mut Storage,
+  result: &mut Vec<u8>,
+) -> ValidityRecord {
+  machine.set_storage(storage.clone());
+
+  let record = match machine.execute(bytecode, block_info) {
+    ExecutionState::Abort(err) => {
+      ValidityRecord::invalid("abort", format!("Execution aborted: {:?}", err))
+    }
+    ExecutionState::Revert => {
+      ValidityRecord::invalid("revert", String::from("Execution reverted"))
+    }
+    ExecutionState::Ok => ValidityRecord {
+      valid: true,
+      state_changed: machine.storage.inner != storage.inner,
+      result: Some(Value::String(hex::encode(&machine.result))),
+      ..Default::default()
+    },
+  };
+  let gas_used = machine
+    .gas_used()
+    .min(three_em_evm::U256::from(u64::MAX))
+    .as_u64();
+  if record.valid {
+    *storage = machine.storage;
+    *result = machine.result;
+  }
+
+  ValidityRecord {
+    gas_used: Some(gas_used),
+    ..record
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::executor::{raw_execute_contract, ExecuteResult};
@@ -489,17 +856,17 @@ mod tests {
   };
   use deno_core::serde_json;
   use deno_core::serde_json::Value;
-  use indexmap::map::IndexMap;
   use std::collections::HashMap;
   use three_em_arweave::arweave::Arweave;
   use three_em_arweave::arweave::{LoadedContract, TransactionData};
   use three_em_arweave::cache::ArweaveCache;
   use three_em_arweave::cache::CacheExt;
+  use three_em_arweave::errors::ContractError;
   use three_em_arweave::gql_result::{
     GQLAmountInterface, GQLBlockInterface, GQLEdgeInterface, GQLNodeInterface,
     GQLOwnerInterface, GQLTagInterface,
   };
-  use three_em_arweave::miscellaneous::ContractType;
+  use three_em_arweave::miscellaneous::{get_sort_key, ContractType};
 
   #[tokio::test]
   async fn test_globals_js() {
@@ -539,11 +906,10 @@ mod tests {
       String::from("10230123021302130"),
       fake_contract,
       fake_interactions,
-      IndexMap::new(),
       None,
       true,
       false,
-      |_, _, _| {
+      |_, _| {
         panic!("not implemented");
       },
       &Arweave::new(
@@ -555,7 +921,8 @@ mod tests {
       HashMap::new(),
       None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(result) = result {
       assert_eq!(
@@ -607,11 +974,10 @@ mod tests {
       String::from("10230123021302130"),
       fake_contract.clone(),
       fake_interactions_2,
-      IndexMap::new(),
       None,
       true,
       false,
-      |_, _, _| {
+      |_, _| {
         panic!("not implemented");
       },
       &Arweave::new(
@@ -623,7 +989,8 @@ mod tests {
       HashMap::new(),
       None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(result) = result {
       assert_eq!(result.errors.len(), 1);
@@ -638,6 +1005,258 @@ mod tests {
     }
   }
 
+  #[tokio::test]
+  async fn test_failed_interaction_rollback() {
+    let fake_contract = generate_fake_loaded_contract_data(
+      include_bytes!("../../testdata/contracts/rollback.js"),
+      ContractType::JAVASCRIPT,
+      serde_json::json!({ "keys": [] }).to_string(),
+    );
+
+    let interaction = |input: Value, id: &str| {
+      generate_fake_interaction(
+        input, id, None, None, None, None, None, None, None, None,
+      )
+    };
+    let interactions = vec![
+      interaction(serde_json::json!({ "key": "a" }), "tx1"),
+      interaction(serde_json::json!({ "key": "b", "fail": true }), "tx2"),
+      interaction(serde_json::json!({ "key": "c" }), "tx3"),
+    ];
+
+    let mut settings = HashMap::new();
+    settings.insert(String::from("EXM"), Value::Bool(true));
+
+    let result = raw_execute_contract(
+      String::from("rollback"),
+      fake_contract,
+      interactions,
+      None,
+      true,
+      false,
+      |_, _| {
+        panic!("not implemented");
+      },
+      &Arweave::new(
+        443,
+        "arweave.net".to_string(),
+        String::from("https"),
+        ArweaveCache::new(),
+      ),
+      settings,
+      None,
+    )
+    .await
+    .unwrap();
+
+    if let ExecuteResult::V8(result) = result {
+      assert_eq!(result.validity.get("tx2").unwrap(), &false);
+      // tx2 counted itself in the globals of the contract before throwing.
+      assert_eq!(
+        result.state,
+        serde_json::json!({ "keys": ["a", "c"], "seen": 2 })
+      );
+      assert_eq!(
+        serde_json::to_value(&result.context.kv).unwrap(),
+        serde_json::json!({ "a": 0, "c": 1 })
+      );
+
+      let failed = result.records.get("tx2").unwrap();
+      assert!(!failed.valid && !failed.state_changed);
+      assert_eq!(failed.error.as_ref().unwrap().kind, "execution");
+      assert!(result.records.get("tx3").unwrap().state_changed);
+      assert_eq!(result.records.len(), result.validity.len());
+    } else {
+      panic!("Unexpected entry");
+    }
+  }
+
+  #[tokio::test]
+  async fn test_failed_evolve() {
+    let fake_contract = generate_fake_loaded_contract_data(
+      include_bytes!("../../testdata/contracts/evolve.js"),
+      ContractType::JAVASCRIPT,
+      serde_json::json!({ "count": 0 }).to_string(),
+    );
+
+    let interaction = |input: Value, id: &str| {
+      generate_fake_interaction(
+        input, id, None, None, None, None, None, None, None, None,
+      )
+    };
+    let interactions = vec![
+      interaction(serde_json::json!({ "function": "count" }), "tx1"),
+      interaction(
+        serde_json::json!({ "function": "evolve", "value": "missing" }),
+        "tx2",
+      ),
+      interaction(serde_json::json!({ "function": "count" }), "tx3"),
+    ];
+
+    // Evolved sources cannot be loaded offline.
+    let mut settings = HashMap::new();
+    settings.insert(String::from("FIXTURES"), serde_json::json!({}));
+
+    let result = raw_execute_contract(
+      String::from("evolve"),
+      fake_contract,
+      interactions,
+      None,
+      true,
+      false,
+      |_, _| {
+        panic!("not implemented");
+      },
+      &Arweave::new(
+        443,
+        "arweave.net".to_string(),
+        String::from("https"),
+        ArweaveCache::new(),
+      ),
+      settings,
+      None,
+    )
+    .await
+    .unwrap();
+
+    if let ExecuteResult::V8(result) = result {
+      assert_eq!(result.state, serde_json::json!({ "count": 2 }));
+      assert!(result.evolutions.is_empty());
+
+      let failed = result.records.get("tx2").unwrap();
+      assert!(!failed.valid && !failed.state_changed);
+      assert_eq!(failed.error.as_ref().unwrap().kind, "evolve");
+      assert!(result.records.get("tx3").unwrap().valid);
+    } else {
+      panic!("Unexpected entry");
+    }
+  }
+
+  #[tokio::test]
+  async fn test_trace() {
+    let fake_contract = generate_fake_loaded_contract_data(
+      include_bytes!("../../testdata/contracts/rollback.js"),
+      ContractType::JAVASCRIPT,
+      serde_json::json!({ "keys": [] }).to_string(),
+    );
+
+    let interaction = |input: Value, id: &str| {
+      generate_fake_interaction(
+        input, id, None, None, None, None, None, None, None, None,
+      )
+    };
+    let interactions = vec![
+      interaction(serde_json::json!({ "key": "a" }), "tx1"),
+      interaction(serde_json::json!({ "key": "b", "fail": true }), "tx2"),
+      interaction(serde_json::json!({ "key": "c" }), "tx3"),
+    ];
+
+    let mut settings = HashMap::new();
+    settings.insert(String::from("EXM"), Value::Bool(true));
+
+    let result = raw_execute_contract(
+      String::from("trace"),
+      fake_contract,
+      interactions,
+      None,
+      true,
+      false,
+      |_, _| {
+        panic!("not implemented");
+      },
+      &Arweave::new(
+        443,
+        "arweave.net".to_string(),
+        String::from("https"),
+        ArweaveCache::new(),
+      )
+      .with_tracing(true),
+      settings,
+      None,
+    )
+    .await
+    .unwrap();
+
+    if let ExecuteResult::V8(result) = result {
+      let trace: Vec<Value> = result
+        .trace
+        .iter()
+        .map(|entry| {
+          serde_json::json!({
+            "id": entry.id,
+            "input": entry.input,
+            "valid": entry.record.valid,
+            "patch": entry.patch,
+          })
+        })
+        .collect();
+
+      assert_eq!(
+        trace,
+        vec![
+          serde_json::json!({
+            "id": "tx1",
+            "input": { "key": "a" },
+            "valid": true,
+            "patch": [
+              { "op": "add", "path": "/keys/0", "value": "a" },
+              { "op": "add", "path": "/seen", "value": 1 }
+            ]
+          }),
+          serde_json::json!({
+            "id": "tx2",
+            "input": { "key": "b", "fail": true },
+            "valid": false,
+            "patch": []
+          }),
+          serde_json::json!({
+            "id": "tx3",
+            "input": { "key": "c" },
+            "valid": true,
+            "patch": [
+              { "op": "add", "path": "/keys/1", "value": "c" },
+              { "op": "replace", "path": "/seen", "value": 2 }
+            ]
+          }),
+        ]
+      );
+    } else {
+      panic!("Unexpected entry");
+    }
+  }
+
+  #[tokio::test]
+  async fn test_invalid_init_state() {
+    let fake_contract = generate_fake_loaded_contract_data(
+      include_bytes!("../../testdata/contracts/counter_error.js"),
+      ContractType::JAVASCRIPT,
+      String::from("{ not json"),
+    );
+
+    let result = raw_execute_contract(
+      String::from("10230123021302130"),
+      fake_contract,
+      vec![],
+      None,
+      true,
+      false,
+      |_, _| {
+        panic!("not implemented");
+      },
+      &Arweave::new(
+        443,
+        "arweave.net".to_string(),
+        String::from("https"),
+        ArweaveCache::new(),
+      ),
+      HashMap::new(),
+      None,
+    )
+    .await;
+
+    assert!(matches!(result, Err(ContractError::InitState { .. })));
+  }
+
   #[tokio::test]
   async fn test_counter_result_js() {
     let init_state = serde_json::json!({
@@ -679,11 +1298,10 @@ mod tests {
       String::from("10230123021302130"),
       fake_contract.clone(),
       fake_interactions,
-      IndexMap::new(),
       None,
       true,
       false,
-      |_, _, _| {
+      |_, _| {
         panic!("not implemented");
       },
       &Arweave::new(
@@ -695,7 +1313,8 @@ mod tests {
       HashMap::new(),
       None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(result) = result {
       assert_eq!(result.result.is_some(), true);
@@ -711,11 +1330,10 @@ mod tests {
       String::from("10230123021302130"),
       fake_contract,
       fake_interactions_2,
-      IndexMap::new(),
       None,
       true,
       false,
-      |_, _, _| {
+      |_, _| {
         panic!("not implemented");
       },
       &Arweave::new(
@@ -727,7 +1345,8 @@ mod tests {
       HashMap::new(),
       None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(result) = result {
       assert_eq!(result.state.get("counts").unwrap().as_i64().unwrap(), 2);
@@ -775,11 +1394,10 @@ mod tests {
       String::from("10230123021302130"),
       fake_contract,
       fake_interactions,
-      IndexMap::new(),
       None,
       true,
       true,
-      |_, _, _| {
+      |_, _| {
         panic!("not implemented");
       },
       &Arweave::new(
@@ -791,7 +1409,8 @@ mod tests {
       HashMap::new(),
       None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(result) = result {
       let x = serde_json::json!({
@@ -868,11 +1487,10 @@ mod tests {
         String::new(),
         fake_contract,
         fake_interactions,
-        IndexMap::new(),
         None,
         true,
         false,
-        |_, _, _| {
+        |_, _| {
           panic!("not implemented");
         },
         &Arweave::new(
@@ -884,7 +1502,8 @@ mod tests {
         HashMap::new(),
         None,
       )
-      .await;
+      .await
+      .unwrap();
 
       result
     };
@@ -978,11 +1597,10 @@ mod tests {
       String::from("Zwp7r7Z10O0TuF6lmFApB7m5lJIrE5RbLAVWg_WKNcU"),
       fake_contract,
       fake_interactions,
-      IndexMap::new(),
       None,
       true,
       false,
-      |_, _, _| {
+      |_, _| {
         panic!("not implemented");
       },
       &Arweave::new(
@@ -994,7 +1612,8 @@ mod tests {
       HashMap::new(),
       None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(result) = result {
       let validity = result.validity;
@@ -1011,6 +1630,14 @@ mod tests {
       assert_eq!(value_state.is_some(), true);
 
       assert_eq!(value.get("v").unwrap(), 0.6666666666666667_f64);
+
+      assert_eq!(result.evolutions.len(), 1);
+      assert_eq!(result.evolutions[0].interaction, "tx1");
+      assert_eq!(result.evolutions[0].sort_key, get_sort_key(&0, "", "tx1"));
+      assert_eq!(
+        result.evolutions[0].source_tx,
+        "C0F9QvOOJNR2DDIicWeL9B-C5vFrtczmOjpW_3FCQBQ"
+      );
     }
   }
 
@@ -1059,11 +1686,10 @@ mod tests {
       String::from("WHATEVA"),
       fake_contract,
       fake_interactions,
-      IndexMap::new(),
       None,
       true,
       false,
-      |_, _, _| {
+      |_, _| {
         panic!("not implemented");
       },
       &Arweave::new(
@@ -1075,7 +1701,8 @@ mod tests {
       HashMap::new(),
       None,
     )
-    .await;
+    .await
+    .unwrap();
 
     if let ExecuteResult::V8(result) = result {
       let value = result.state;
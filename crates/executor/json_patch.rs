@@ -0,0 +1,195 @@
+use deno_core::error::AnyError;
+use deno_core::serde_json::Value;
+use serde::{Deserialize, Serialize};
+
+/// Operation of an RFC 6902 JSON Patch.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+  Add { path: String, value: Value },
+  Remove { path: String },
+  Replace { path: String, value: Value },
+}
+
+// Escapes a key as a JSON Pointer reference token (RFC 6901).
+fn escape(key: &str) -> String {
+  key.replace('~', "~0").replace('/', "~1")
+}
+
+/// JSON Patch that turns `from` into `to`.
+///
+/// Objects are compared key by key and arrays index by index. Elements
+/// past the end of the shorter array are removed from the back, or
+/// appended in order.
+pub fn diff(from: &Value, to: &Value) -> Vec<PatchOperation> {
+  let mut operations = Vec::new();
+  diff_at("", from, to, &mut operations);
+  operations
+}
+
+fn diff_at(
+  path: &str,
+  from: &Value,
+  to: &Value,
+  operations: &mut Vec<PatchOperation>,
+) {
+  match (from, to) {
+    (Value::Object(from), Value::Object(to)) => {
+      for (key, from_value) in from {
+        let path = format!("{}/{}", path, escape(key));
+        match to.get(key) {
+          Some(to_value) => diff_at(&path, from_value, to_value, operations),
+          None => operations.push(PatchOperation::Remove { path }),
+        }
+      }
+      for (key, to_value) in to {
+        if !from.contains_key(key) {
+          operations.push(PatchOperation::Add {
+            path: format!("{}/{}", path, escape(key)),
+            value: to_value.to_owned(),
+          });
+        }
+      }
+    }
+    (Value::Array(from), Value::Array(to)) => {
+      let common = from.len().min(to.len());
+      for index in 0..common {
+        let path = format!("{}/{}", path, index);
+        diff_at(&path, &from[index], &to[index], operations);
+      }
+      for index in (common..from.len()).rev() {
+        operations.push(PatchOperation::Remove {
+          path: format!("{}/{}", path, index),
+        });
+      }
+      for (index, to_value) in to.iter().enumerate().skip(common) {
+        operations.push(PatchOperation::Add {
+          path: format!("{}/{}", path, index),
+          value: to_value.to_owned(),
+        });
+      }
+    }
+    _ if from == to => {}
+    _ => operations.push(PatchOperation::Replace {
+      path: path.to_owned(),
+      value: to.to_owned(),
+    }),
+  }
+}
+
+/// Applies a patch produced by [`diff`] to `value`.
+pub fn apply(
+  value: &mut Value,
+  operations: &[PatchOperation],
+) -> Result<(), AnyError> {
+  for operation in operations {
+    let (path, new_value) = match operation {
+      PatchOperation::Add { path, value } => (path, Some(value)),
+      PatchOperation::Remove { path } => (path, None),
+      PatchOperation::Replace { path, value } => (path, Some(value)),
+    };
+    let invalid_path = || AnyError::msg(format!("Invalid patch path {}", path));
+
+    let (parent, token) = match path.rsplit_once('/') {
+      Some(split) => split,
+      None => {
+        *value = new_value.cloned().ok_or_else(invalid_path)?;
+        continue;
+      }
+    };
+    let token = token.replace("~1", "/").replace("~0", "~");
+    let parent = value.pointer_mut(parent).ok_or_else(invalid_path)?;
+
+    match (parent, new_value) {
+      (Value::Object(map), None) => {
+        map.remove(&token).ok_or_else(invalid_path)?;
+      }
+      (Value::Object(map), Some(new_value)) => {
+        map.insert(token, new_value.to_owned());
+      }
+      (Value::Array(array), new_value) => {
+        let index: usize = token.parse().map_err(|_| invalid_path())?;
+        let in_bounds = match operation {
+          PatchOperation::Add { .. } => index <= array.len(),
+          _ => index < array.len(),
+        };
+        if !in_bounds {
+          return Err(invalid_path());
+        }
+
+        match (operation, new_value) {
+          (PatchOperation::Add { .. }, Some(new_value)) => {
+            array.insert(index, new_value.to_owned())
+          }
+          (_, Some(new_value)) => array[index] = new_value.to_owned(),
+          (_, None) => {
+            array.remove(index);
+          }
+        }
+      }
+      _ => return Err(invalid_path()),
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::json_patch::{apply, diff, PatchOperation};
+  use deno_core::serde_json;
+  use deno_core::serde_json::Value;
+
+  #[test]
+  fn test_diff() {
+    let from = serde_json::json!({
+      "a/b~c": 1,
+      "balances": { "alice": 10, "bob": 5 },
+      "holders": ["alice", "bob", "carol"],
+      "name": "Token"
+    });
+    let to = serde_json::json!({
+      "a/b~c": 2,
+      "balances": { "alice": 7, "dave": 3 },
+      "holders": ["alice"],
+      "name": "Token"
+    });
+
+    let operations = diff(&from, &to);
+    assert_eq!(
+      serde_json::to_value(&operations).unwrap(),
+      serde_json::json!([
+        { "op": "replace", "path": "/a~1b~0c", "value": 2 },
+        { "op": "replace", "path": "/balances/alice", "value": 7 },
+        { "op": "remove", "path": "/balances/bob" },
+        { "op": "add", "path": "/balances/dave", "value": 3 },
+        { "op": "remove", "path": "/holders/2" },
+        { "op": "remove", "path": "/holders/1" }
+      ])
+    );
+
+    let mut patched = from.clone();
+    apply(&mut patched, &operations).unwrap();
+    assert_eq!(patched, to);
+
+    let mut patched = to.clone();
+    apply(&mut patched, &diff(&to, &from)).unwrap();
+    assert_eq!(patched, from);
+
+    assert!(diff(&from, &from).is_empty());
+    assert!(apply(
+      &mut patched,
+      &[PatchOperation::Remove {
+        path: String::from("/holders/9")
+      }]
+    )
+    .is_err());
+    assert_eq!(
+      diff(&from, &Value::Null),
+      vec![PatchOperation::Replace {
+        path: String::new(),
+        value: Value::Null
+      }]
+    );
+  }
+}
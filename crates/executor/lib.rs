@@ -1,12 +1,15 @@
+pub mod diff;
 pub mod executor;
+pub mod json_patch;
+pub mod requirements;
+pub mod runtime;
 pub mod test_util;
+pub mod trace;
 pub mod utils;
 
 pub use crate::executor::ExecuteResult;
 pub use crate::executor::ValidityTable;
 use crate::executor::{raw_execute_contract, V8Result};
-use deno_core::error::{generic_error, AnyError};
-use deno_core::serde_json::Value;
 pub use indexmap::map::IndexMap;
 use lru::LruCache;
 use once_cell::sync::Lazy;
@@ -17,9 +20,12 @@ use std::sync::Mutex;
 use three_em_arweave::arweave::Arweave;
 use three_em_arweave::arweave::LoadedContract;
 use three_em_arweave::arweave::{get_cache, ManualLoadedContract};
+use three_em_arweave::cache::StateResult;
+pub use three_em_arweave::errors::ContractError;
 use three_em_arweave::gql_result::GQLEdgeInterface;
 use three_em_arweave::gql_result::GQLNodeInterface;
 use three_em_arweave::miscellaneous::get_sort_key;
+pub use three_em_arweave::miscellaneous::{ValidityRecord, ValidityRecords};
 use three_em_evm::Instruction;
 use three_em_evm::U256;
 /**
@@ -54,7 +60,7 @@ pub async fn simulate_contract(
   maybe_settings: Option<HashMap<String, deno_core::serde_json::Value>>,
   maybe_exm_context_str: Option<String>,
   maybe_contract_source: Option<ManualLoadedContract>,
-) -> Result<ExecuteResult, AnyError> {
+) -> Result<ExecuteResult, ContractError> {
   let shared_id = contract_id.clone();
   /**
    * Two Options
@@ -69,16 +75,19 @@ pub async fn simulate_contract(
   // There is no reason to join - you join here to run async tasks concurrently.
   let loaded_contract = tokio::join!(async move {
     if let Some(contract_source) = maybe_contract_source {
-      let contract = LoadedContract {
-        contract_src: contract_source.contract_src,
-        contract_type: contract_source.contract_type,
-        init_state: contract_init_state.clone().unwrap(),
-        ..Default::default()
-      };
-
-      Ok(contract)
+      contract_init_state
+        .map(|init_state| LoadedContract {
+          contract_src: contract_source.contract_src,
+          contract_type: contract_source.contract_type,
+          init_state,
+          ..Default::default()
+        })
+        .ok_or_else(|| ContractError::InitState {
+          contract: shared_id,
+          reason: String::from("an init state is required with a source"),
+        })
     } else {
-      let contract: Result<LoadedContract, AnyError> = arweave
+      let contract: Result<LoadedContract, ContractError> = arweave
         .load_contract(
           shared_id,
           None,
@@ -107,49 +116,42 @@ pub async fn simulate_contract(
     deno_core::serde_json::Value::Bool(true),
   );
 
+  let loaded_contract = loaded_contract?;
+
   /**
    *
    * maybe_exm_context_str != None, save it
    *
    *
    */
-  if loaded_contract.is_ok() {
-    let maybe_exm_context = {
-      if let Some(context_str) = maybe_exm_context_str {
-        Some(deno_core::serde_json::from_str(&context_str[..]).unwrap()) //convert the context_str into JSON
-      } else {
-        None
-      }
-    };
-
-    let execute = raw_execute_contract(
-      contract_id,
-      loaded_contract.unwrap(),
-      interactions,
-      IndexMap::new(),
-      None,
-      true,
-      false,
-      |validity_table, cache_state, errors| {
-        ExecuteResult::V8(V8Result {
-          state: cache_state.unwrap(),
-          validity: validity_table,
-          context: Default::default(),
-          result: None,
-          updated: true,
-          errors,
-        })
-      },
-      arweave,
-      settings,
-      maybe_exm_context,
-    )
-    .await;
+  let maybe_exm_context = match maybe_exm_context_str {
+    //convert the context_str into JSON
+    Some(context_str) => Some(
+      deno_core::serde_json::from_str(&context_str[..])
+        .map_err(|err| ContractError::ExmContext(err.to_string()))?,
+    ),
+    None => None,
+  };
+
+  raw_execute_contract(
+    contract_id,
+    loaded_contract,
+    interactions,
+    None,
+    true,
+    false,
+    |_, _| unreachable!("simulated interactions are always processed"),
+    arweave,
+    settings,
+    maybe_exm_context,
+  )
+  .await
+}
 
-    Ok(execute)
-  } else {
-    Err(generic_error("Contract could not be loaded"))
-  }
+/// Forgets the result of `contract_id` kept in memory, so that its next
+/// evaluation picks up the interactions that arrived since.
+pub fn invalidate_result(contract_id: &str) {
+  LRU_CACHE.lock().unwrap().pop(contract_id);
 }
 
 #[async_recursion::async_recursion(?Send)]
@@ -161,7 +163,7 @@ pub async fn execute_contract(
   contract_src_tx: Option<String>,
   contract_content_type: Option<String>,
   arweave: &Arweave,
-) -> Result<ExecuteResult, AnyError> {
+) -> Result<ExecuteResult, ContractError> {
   // Need to see how getting contract_id from LRU_CACHE yields result variable
   if let Some(result) = LRU_CACHE.lock().unwrap().get(&contract_id) {
     return Ok(result.clone());
@@ -175,7 +177,7 @@ pub async fn execute_contract(
   // each async move will return a piece of the tuple (loaded_contract, interactions)
   let (loaded_contract, interactions) = tokio::join!(
     async move {
-      let contract: Result<LoadedContract, AnyError> = arweave
+      let contract: Result<LoadedContract, ContractError> = arweave
         .load_contract(
           shared_id,
           contract_src_tx,
@@ -190,10 +192,12 @@ pub async fn execute_contract(
       contract
     },
     async move {
-      let interactions: Result<(Vec<GQLEdgeInterface>, usize, bool), AnyError> =
-        arweave
-          .get_interactions(contract_id_copy2, height, cache)
-          .await;
+      let interactions: Result<
+        (Vec<GQLEdgeInterface>, usize, bool),
+        ContractError,
+      > = arweave
+        .get_interactions(contract_id_copy2, height, cache)
+        .await;
       let (
         result_interactions,
         new_interaction_index,
@@ -208,7 +212,7 @@ pub async fn execute_contract(
         interactions,
         new_interaction_index,
         are_there_new_interactions,
-      )) as Result<(Vec<GQLEdgeInterface>, usize, bool), AnyError>
+      )) as Result<(Vec<GQLEdgeInterface>, usize, bool), ContractError>
     }
   );
 
@@ -218,27 +222,20 @@ pub async fn execute_contract(
 
   let mut interactions = result_interactions;
 
-  let mut validity: IndexMap<String, Value> = IndexMap::new();
-
-  let mut needs_processing = true;
-  let mut cache_state: Option<Value> = None;
-
-  if cache {
-    let get_cached_state = get_cache()
+  // The cached state carries the validity, evolutions, records and
+  // requests of the interactions evaluated before it.
+  let cached: Option<StateResult> = if cache {
+    get_cache()
       .lock()
       .unwrap()
-      .find_state(contract_id_copy.to_owned());
-
-    if let Some(cached_state) = get_cached_state {
-      cache_state = Some(cached_state.state);
-      validity = cached_state.validity;
-      needs_processing = are_there_new_interactions;
-    }
-  }
+      .find_state(contract_id_copy.to_owned())
+  } else {
+    None
+  };
 
-  let is_cache_state_present = cache_state.is_some();
+  let needs_processing = cached.is_none() || are_there_new_interactions;
 
-  if cache && is_cache_state_present && are_there_new_interactions {
+  if cached.is_some() && are_there_new_interactions {
     interactions = (&interactions[new_interaction_index..]).to_vec();
   }
 
@@ -246,25 +243,27 @@ pub async fn execute_contract(
     contract_id_copy.to_owned(),
     loaded_contract,
     interactions,
-    validity,
-    cache_state,
+    cached,
     needs_processing,
     show_errors,
-    |validity_table, cache_state, errors| {
+    |cached, errors| {
       ExecuteResult::V8(V8Result {
-        state: cache_state.unwrap(),
-        validity: validity_table,
+        state: cached.state,
+        validity: cached.validity,
         context: Default::default(),
         result: None,
         updated: false,
         errors: errors,
+        evolutions: cached.evolutions,
+        records: cached.records,
+        trace: Vec::new(),
       })
     },
     arweave,
     HashMap::new(),
     None,
   )
-  .await;
+  .await?;
 
   LRU_CACHE.lock().unwrap().put(contract_id, result.clone());
 
@@ -283,6 +282,32 @@ pub fn get_input_from_interaction(interaction_tx: &GQLNodeInterface) -> &str {
   }
 }
 
+/// Input a transaction passes to `contract_id`. When it interacts with
+/// several contracts, each `Input` tag belongs to the `Contract` tag right
+/// before it and, as in SmartWeave, only the first pair addressed to the
+/// contract is evaluated.
+pub fn get_input_for_contract<'a>(
+  interaction_tx: &'a GQLNodeInterface,
+  contract_id: &str,
+) -> Option<&'a str> {
+  if !has_multiple_interactions(interaction_tx) {
+    return match get_input_from_interaction(interaction_tx) {
+      "" => None,
+      input => Some(input),
+    };
+  }
+
+  interaction_tx
+    .tags
+    .windows(2)
+    .find(|pair| {
+      pair[0].name == "Contract"
+        && pair[0].value == contract_id
+        && pair[1].name == "Input"
+    })
+    .map(|pair| pair[1].value.as_str())
+}
+
 pub fn has_multiple_interactions(interaction_tx: &GQLNodeInterface) -> bool {
   let tags = (&interaction_tx.tags).to_owned();
   let count = tags
@@ -313,7 +338,7 @@ pub fn sort_interactions(interactions: &mut Vec<GQLEdgeInterface>) {
   });
 }
 
-fn nop_cost_fn(_: &Instruction) -> U256 {
+pub fn nop_cost_fn(_: &Instruction) -> U256 {
   U256::zero()
 }
 
@@ -321,7 +346,7 @@ fn nop_cost_fn(_: &Instruction) -> U256 {
 mod test {
   use crate::test_util::generate_fake_interaction;
   use crate::ExecuteResult;
-  use crate::{execute_contract, sort_interactions};
+  use crate::{execute_contract, get_input_for_contract, sort_interactions};
   use deno_core::serde_json;
   use deno_core::serde_json::value::Value::Null;
   use deno_core::serde_json::Value;
@@ -332,7 +357,7 @@ mod test {
   use three_em_arweave::arweave::Arweave;
   use three_em_arweave::cache::ArweaveCache;
   use three_em_arweave::cache::CacheExt;
-  use three_em_arweave::gql_result::GQLEdgeInterface;
+  use three_em_arweave::gql_result::{GQLEdgeInterface, GQLTagInterface};
 
   #[derive(Deserialize, Serialize)]
   struct People {
@@ -365,6 +390,46 @@ mod test {
     assert_eq!(loaded_contract.contract_transaction.format, 2);
   }
 
+  #[test]
+  fn test_get_input_for_contract() {
+    let tag = |name: &str, value: &str| GQLTagInterface {
+      name: String::from(name),
+      value: String::from(value),
+    };
+
+    let mut interaction = generate_fake_interaction(
+      serde_json::json!({ "function": "transfer" }),
+      "tx1",
+      None,
+      None,
+      None,
+      None,
+      None,
+      None,
+      None,
+      None,
+    )
+    .node;
+    assert_eq!(
+      get_input_for_contract(&interaction, "A"),
+      Some(r#"{"function":"transfer"}"#)
+    );
+
+    interaction.tags = vec![
+      tag("App-Name", "SmartWeaveAction"),
+      tag("Contract", "A"),
+      tag("Input", "1"),
+      tag("Contract", "B"),
+      tag("Input", "2"),
+      tag("Contract", "A"),
+      tag("Input", "3"),
+      tag("Contract", "A"),
+    ];
+    assert_eq!(get_input_for_contract(&interaction, "A"), Some("1"));
+    assert_eq!(get_input_for_contract(&interaction, "B"), Some("2"));
+    assert_eq!(get_input_for_contract(&interaction, "C"), None);
+  }
+
   #[tokio::test]
   async fn test_sorting() {
     // expected:  j7Q8fkIG1mWnZYt8A0eYP46pGXV8sQXBBO51vqOjeGI, mFSUswFVKO8vPU4igACglukRxRuEGH4_ZJ89VdJHnNo, YFlMzDiiGLJvRnS2VSDzqRA5Zv551o-oW29R-FCIj8U
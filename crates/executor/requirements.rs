@@ -0,0 +1,265 @@
+use deno_core::serde_json;
+use serde::Serialize;
+use three_em_arweave::arweave::LoadedContract;
+use three_em_arweave::gql_result::{GQLAmountInterface, GQLNodeInterface};
+use three_em_arweave::miscellaneous::{InteractionError, ValidityRecord};
+
+/// Conditions an interaction must meet before the contract handles it.
+#[derive(Clone, Debug, Default)]
+pub struct InteractionRequirements {
+  /// Minimum fee in winston, from the `Min-Fee` tag.
+  pub min_fee: Option<u128>,
+  /// Wallet interactions must transfer to, from the `Interaction-Target`
+  /// tag.
+  pub target: Option<String>,
+  /// Minimum quantity in winston, from the `Min-Quantity` tag.
+  pub min_quantity: Option<u128>,
+}
+
+/// Why an interaction does not meet the requirements of the contract.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum RequirementError {
+  InsufficientFee {
+    required: String,
+    provided: String,
+  },
+  WrongTarget {
+    required: String,
+    provided: Option<String>,
+  },
+  InsufficientQuantity {
+    required: String,
+    provided: String,
+  },
+  /// The contract sets a requirement tag that is not an amount in winston.
+  InvalidRequirement {
+    tag: String,
+    value: String,
+  },
+}
+
+impl std::fmt::Display for RequirementError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RequirementError::InsufficientFee { required, provided } => write!(
+        f,
+        "Fee of {} winston is below the minimum of {}",
+        provided, required
+      ),
+      RequirementError::WrongTarget { required, provided } => write!(
+        f,
+        "Target {} does not match the required {}",
+        provided.as_deref().unwrap_or("(none)"),
+        required
+      ),
+      RequirementError::InsufficientQuantity { required, provided } => write!(
+        f,
+        "Quantity of {} winston is below the minimum of {}",
+        provided, required
+      ),
+      RequirementError::InvalidRequirement { tag, value } => write!(
+        f,
+        "{} of the contract is not an amount in winston: {}",
+        tag, value
+      ),
+    }
+  }
+}
+
+impl RequirementError {
+  /// Invalid record whose error kind is the reason, with the structured
+  /// reason as details.
+  pub fn to_record(&self) -> ValidityRecord {
+    let details = serde_json::to_value(self).unwrap();
+    ValidityRecord {
+      valid: false,
+      error: Some(InteractionError {
+        kind: details["reason"].as_str().unwrap_or_default().to_owned(),
+        message: self.to_string(),
+        details: Some(details),
+      }),
+      ..Default::default()
+    }
+  }
+}
+
+fn winston(amount: &Option<GQLAmountInterface>) -> u128 {
+  amount
+    .as_ref()
+    .and_then(|amount| amount.winston.as_ref())
+    .and_then(|winston| winston.parse().ok())
+    .unwrap_or(0)
+}
+
+fn parse_amount(
+  tag: &str,
+  value: Option<String>,
+) -> Result<Option<u128>, RequirementError> {
+  value
+    .map(|value| {
+      value
+        .parse()
+        .map_err(|_| RequirementError::InvalidRequirement {
+          tag: tag.to_owned(),
+          value,
+        })
+    })
+    .transpose()
+}
+
+impl InteractionRequirements {
+  /// Requirements set by the tags of the contract. An amount that cannot be
+  /// parsed is an error, rather than a requirement that is never enforced.
+  pub fn from_contract(
+    contract: &LoadedContract,
+  ) -> Result<InteractionRequirements, RequirementError> {
+    let tag = |name: &str| contract.contract_transaction.get_tag(name).ok();
+
+    Ok(InteractionRequirements {
+      min_fee: parse_amount("Min-Fee", contract.min_fee.to_owned())?,
+      target: tag("Interaction-Target"),
+      min_quantity: parse_amount("Min-Quantity", tag("Min-Quantity"))?,
+    })
+  }
+
+  pub fn check(
+    &self,
+    interaction: &GQLNodeInterface,
+  ) -> Result<(), RequirementError> {
+    if let Some(min_fee) = self.min_fee {
+      let fee = winston(&interaction.fee);
+      if fee < min_fee {
+        return Err(RequirementError::InsufficientFee {
+          required: min_fee.to_string(),
+          provided: fee.to_string(),
+        });
+      }
+    }
+
+    if let Some(target) = &self.target {
+      if interaction.recipient.as_ref() != Some(target) {
+        return Err(RequirementError::WrongTarget {
+          required: target.to_owned(),
+          provided: interaction.recipient.to_owned(),
+        });
+      }
+    }
+
+    if let Some(min_quantity) = self.min_quantity {
+      let quantity = winston(&interaction.quantity);
+      if quantity < min_quantity {
+        return Err(RequirementError::InsufficientQuantity {
+          required: min_quantity.to_string(),
+          provided: quantity.to_string(),
+        });
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::requirements::{InteractionRequirements, RequirementError};
+  use crate::test_util::{
+    generate_fake_interaction, generate_fake_loaded_contract_data,
+  };
+  use deno_core::serde_json;
+  use three_em_arweave::gql_result::GQLAmountInterface;
+  use three_em_arweave::miscellaneous::ContractType;
+
+  fn amount(winston: &str) -> Option<GQLAmountInterface> {
+    Some(GQLAmountInterface {
+      winston: Some(String::from(winston)),
+      ar: None,
+    })
+  }
+
+  #[test]
+  fn test_check_requirements() {
+    let requirements = InteractionRequirements {
+      min_fee: Some(100),
+      target: Some(String::from("RECIPIENT1234")),
+      min_quantity: Some(10),
+    };
+    let interaction = |recipient: &str, quantity: &str, fee: &str| {
+      generate_fake_interaction(
+        serde_json::json!({}),
+        "tx1",
+        None,
+        None,
+        None,
+        Some(String::from(recipient)),
+        None,
+        amount(quantity),
+        amount(fee),
+        None,
+      )
+      .node
+    };
+
+    assert!(requirements
+      .check(&interaction("RECIPIENT1234", "10", "100"))
+      .is_ok());
+    assert_eq!(
+      requirements.check(&interaction("RECIPIENT1234", "10", "99")),
+      Err(RequirementError::InsufficientFee {
+        required: String::from("100"),
+        provided: String::from("99"),
+      })
+    );
+    assert_eq!(
+      requirements.check(&interaction("SOMEONE", "10", "100")),
+      Err(RequirementError::WrongTarget {
+        required: String::from("RECIPIENT1234"),
+        provided: Some(String::from("SOMEONE")),
+      })
+    );
+
+    let err = requirements
+      .check(&interaction("RECIPIENT1234", "9", "100"))
+      .unwrap_err();
+    assert_eq!(
+      serde_json::to_value(&err).unwrap(),
+      serde_json::json!({
+        "reason": "insufficient_quantity",
+        "required": "10",
+        "provided": "9"
+      })
+    );
+    let record = err.to_record();
+    assert_eq!(record.error.unwrap().kind, "insufficient_quantity");
+    assert!(!record.valid);
+    assert!(InteractionRequirements::default()
+      .check(&interaction("", "0", "0"))
+      .is_ok());
+  }
+
+  #[test]
+  fn test_invalid_min_fee() {
+    let mut contract = generate_fake_loaded_contract_data(
+      b"",
+      ContractType::JAVASCRIPT,
+      String::from("{}"),
+    );
+    contract.min_fee = Some(String::from("100"));
+    assert_eq!(
+      InteractionRequirements::from_contract(&contract)
+        .unwrap()
+        .min_fee,
+      Some(100)
+    );
+
+    // Fees are in winston, not AR.
+    contract.min_fee = Some(String::from("0.5"));
+    assert_eq!(
+      InteractionRequirements::from_contract(&contract).unwrap_err(),
+      RequirementError::InvalidRequirement {
+        tag: String::from("Min-Fee"),
+        value: String::from("0.5"),
+      }
+    );
+  }
+}
@@ -0,0 +1,302 @@
+use crate::executor::{get_execution_context, op_smartweave_read_contract};
+use deno_core::error::AnyError;
+use deno_core::serde_json;
+use deno_core::serde_json::Value;
+use std::collections::HashMap;
+use three_em_arweave::errors::ContractError;
+use three_em_arweave::miscellaneous::ContractType;
+use three_em_exm_base_ops::kv::KvStore;
+use three_em_exm_base_ops::{ExmContext, RequestStore};
+use three_em_js::CallResult;
+use three_em_js::Runtime;
+use three_em_smartweave::InteractionContext;
+use three_em_wasm::WasmRuntime;
+
+/// Outcome of an interaction that did not throw.
+pub enum CallOutcome {
+  Applied {
+    result: Option<Value>,
+    state_updated: bool,
+  },
+  /// The new state has `canEvolve` set and points `evolve` to a source.
+  Evolve {
+    source: String,
+    result: Option<Value>,
+  },
+}
+
+/// Runtime of a JavaScript or WASM contract. Both keep their state as
+/// JSON, which lets an evolving contract switch from one to the other.
+/// A WASM runtime also keeps the state before the interaction in progress
+/// once it changes.
+pub enum ContractRuntime {
+  Js(Runtime),
+  Wasm(WasmRuntime, Vec<u8>, Option<Vec<u8>>),
+}
+
+/// Source transaction the state asks the contract to evolve to, if any.
+pub fn get_evolve_source(state: &Value) -> Option<String> {
+  if state.get("canEvolve").and_then(Value::as_bool) != Some(true) {
+    return None;
+  }
+
+  state
+    .get("evolve")
+    .and_then(Value::as_str)
+    .filter(|source| !source.is_empty())
+    .map(String::from)
+}
+
+impl ContractRuntime {
+  pub async fn new(
+    contract_id: &str,
+    contract_type: &ContractType,
+    contract_src: &[u8],
+    state: Value,
+    arweave_info: (i32, String, String),
+    settings: HashMap<String, Value>,
+    maybe_exm_context: Option<Value>,
+  ) -> Result<ContractRuntime, ContractError> {
+    let runtime_error = |reason: String| ContractError::RuntimeInit {
+      contract: contract_id.to_owned(),
+      reason,
+    };
+
+    match contract_type {
+      ContractType::JAVASCRIPT => {
+        let source = std::str::from_utf8(contract_src).map_err(|err| {
+          ContractError::SourceEncoding {
+            contract: contract_id.to_owned(),
+            reason: err.to_string(),
+          }
+        })?;
+
+        let rt = Runtime::new(
+          source,
+          state,
+          arweave_info,
+          op_smartweave_read_contract::decl(),
+          settings,
+          maybe_exm_context,
+        )
+        .await
+        .map_err(|err| runtime_error(err.to_string()))?;
+
+        Ok(ContractRuntime::Js(rt))
+      }
+      ContractType::WASM => {
+        let rt = WasmRuntime::new(contract_src)
+          .map_err(|err| runtime_error(err.to_string()))?;
+        let state = serde_json::to_vec(&state)
+          .map_err(|err| runtime_error(err.to_string()))?;
+
+        Ok(ContractRuntime::Wasm(rt, state, None))
+      }
+      ContractType::EVM => Err(runtime_error(String::from(
+        "EVM contracts cannot share state with JavaScript or WASM contracts",
+      ))),
+    }
+  }
+
+  pub async fn call(
+    &mut self,
+    input: Value,
+    interaction_context: InteractionContext,
+  ) -> Result<CallOutcome, AnyError> {
+    match self {
+      ContractRuntime::Js(rt) => {
+        match rt.call(input, Some(interaction_context)).await? {
+          None => Ok(CallOutcome::Applied {
+            result: None,
+            state_updated: false,
+          }),
+          // The runtime reports every state with `canEvolve` set, whether
+          // or not it names a source to evolve to.
+          Some(CallResult::Evolve(_, result)) => {
+            let result =
+              result.and_then(|result| rt.to_value::<Value>(&result).ok());
+            match get_evolve_source(&rt.get_contract_state()?) {
+              Some(source) => Ok(CallOutcome::Evolve { source, result }),
+              None => Ok(CallOutcome::Applied {
+                result,
+                state_updated: true,
+              }),
+            }
+          }
+          Some(CallResult::Result(result, state_updated)) => {
+            Ok(CallOutcome::Applied {
+              result: rt.to_value::<Value>(&result).ok(),
+              state_updated,
+            })
+          }
+        }
+      }
+      ContractRuntime::Wasm(rt, state, previous) => {
+        let mut action = serde_json::to_vec(&input)?;
+        let new_state = rt.call(state, &mut action, interaction_context)?;
+        let state_updated = new_state != *state;
+        if state_updated {
+          let state = std::mem::replace(state, new_state);
+          previous.get_or_insert(state);
+        }
+
+        let evolve_source = if state_updated {
+          serde_json::from_slice::<Value>(state)
+            .ok()
+            .and_then(|state| get_evolve_source(&state))
+        } else {
+          None
+        };
+
+        match evolve_source {
+          Some(source) => Ok(CallOutcome::Evolve {
+            source,
+            result: None,
+          }),
+          None => Ok(CallOutcome::Applied {
+            result: None,
+            state_updated,
+          }),
+        }
+      }
+    }
+  }
+
+  /// Metered cost of the calls so far. JavaScript contracts are not
+  /// metered.
+  pub fn cost(&mut self) -> Option<u64> {
+    match self {
+      ContractRuntime::Js(_) => None,
+      ContractRuntime::Wasm(rt, ..) => Some(rt.get_cost() as u64),
+    }
+  }
+
+  /// Makes the responses recorded for an interaction available for replay,
+  /// and starts journaling its state changes, KV writes and requests.
+  pub fn begin_interaction(
+    &mut self,
+    responses: HashMap<String, Value>,
+  ) -> Result<(), AnyError> {
+    match self {
+      ContractRuntime::Js(rt) => {
+        {
+          let op_state = rt.op_state();
+          let mut op_state = op_state.borrow_mut();
+          op_state.put(RequestStore::new(responses));
+          if let Some(kv) = op_state.try_borrow_mut::<KvStore>() {
+            kv.begin();
+          }
+        }
+        rt.begin_interaction()?;
+      }
+      ContractRuntime::Wasm(_, _, previous) => *previous = None,
+    }
+    Ok(())
+  }
+
+  /// Reverts the state changes, KV writes and requests of an interaction
+  /// that failed.
+  pub fn rollback_interaction(&mut self) -> Result<(), AnyError> {
+    match self {
+      ContractRuntime::Js(rt) => {
+        {
+          let op_state = rt.op_state();
+          let mut op_state = op_state.borrow_mut();
+          if let Some(kv) = op_state.try_borrow_mut::<KvStore>() {
+            kv.rollback();
+          }
+        }
+        rt.rollback_interaction()?;
+      }
+      ContractRuntime::Wasm(_, state, previous) => {
+        if let Some(previous) = previous.take() {
+          *state = previous;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Responses recorded while evaluating the interaction.
+  pub fn end_interaction(&mut self) -> RequestStore {
+    match self {
+      ContractRuntime::Js(rt) => rt
+        .op_state()
+        .borrow_mut()
+        .try_take::<RequestStore>()
+        .unwrap_or_default(),
+      // WASM contracts have no deterministic fetch.
+      ContractRuntime::Wasm(..) => RequestStore::default(),
+    }
+  }
+
+  pub fn get_state(&mut self) -> Result<Value, AnyError> {
+    match self {
+      ContractRuntime::Js(rt) => rt.get_contract_state(),
+      ContractRuntime::Wasm(_, state, _) => Ok(serde_json::from_slice(state)?),
+    }
+  }
+
+  pub fn get_exm_context(&mut self) -> ExmContext {
+    match self {
+      ContractRuntime::Js(rt) => {
+        get_execution_context(rt.get_exm_context::<ExmContext>())
+      }
+      // TODO: WASM Context
+      ContractRuntime::Wasm(..) => Default::default(),
+    }
+  }
+}
+
+/// EXM context of a runtime rebuilt during an evaluation: the initial
+/// context, with the requests and KV entries recorded since.
+pub fn rebuild_exm_context(
+  initial: Option<Value>,
+  context: ExmContext,
+) -> Result<Option<Value>, AnyError> {
+  let mut rebuilt = match initial {
+    Some(Value::Object(initial)) => initial,
+    _ => serde_json::Map::new(),
+  };
+
+  let requests = rebuilt
+    .entry("requests")
+    .or_insert_with(|| Value::Object(serde_json::Map::new()));
+  if let Value::Object(requests) = requests {
+    for (hash, response) in context.requests {
+      requests.insert(hash, serde_json::to_value(response)?);
+    }
+  }
+  rebuilt.insert(String::from("kv"), serde_json::to_value(context.kv)?);
+
+  Ok(Some(Value::Object(rebuilt)))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::runtime::get_evolve_source;
+  use deno_core::serde_json;
+
+  #[test]
+  fn test_get_evolve_source() {
+    assert_eq!(
+      get_evolve_source(&serde_json::json!({
+        "canEvolve": true,
+        "evolve": "C0F9QvOOJNR2DDIicWeL9B-C5vFrtczmOjpW_3FCQBQ"
+      })),
+      Some(String::from("C0F9QvOOJNR2DDIicWeL9B-C5vFrtczmOjpW_3FCQBQ"))
+    );
+    assert_eq!(
+      get_evolve_source(&serde_json::json!({
+        "canEvolve": false,
+        "evolve": "C0F9QvOOJNR2DDIicWeL9B-C5vFrtczmOjpW_3FCQBQ"
+      })),
+      None
+    );
+    assert_eq!(
+      get_evolve_source(&serde_json::json!({ "canEvolve": true })),
+      None
+    );
+    assert_eq!(get_evolve_source(&serde_json::json!([])), None);
+  }
+}
@@ -0,0 +1,91 @@
+use crate::get_input_for_contract;
+use crate::json_patch::{diff, PatchOperation};
+use deno_core::error::AnyError;
+use deno_core::serde_json;
+use deno_core::serde_json::Value;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use three_em_arweave::gql_result::GQLNodeInterface;
+use three_em_arweave::miscellaneous::{get_sort_key, ValidityRecord};
+
+/// How a single interaction changed the state of a contract.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceEntry {
+  pub id: String,
+  pub sort_key: String,
+  pub caller: String,
+  /// Input passed to the contract, or null if the transaction has none.
+  pub input: Value,
+  #[serde(flatten)]
+  pub record: ValidityRecord,
+  /// RFC 6902 JSON Patch from the state before the interaction to the
+  /// state after it.
+  pub patch: Vec<PatchOperation>,
+}
+
+/// Trace of the interactions evaluated for a contract.
+pub struct Trace {
+  contract_id: String,
+  state: Value,
+  entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+  pub fn new(contract_id: &str, state: Value) -> Trace {
+    Trace {
+      contract_id: contract_id.to_owned(),
+      state,
+      entries: Vec::new(),
+    }
+  }
+
+  /// Adds an interaction along with the state it left behind, or `None` if
+  /// it did not change the state.
+  pub fn push(
+    &mut self,
+    tx: &GQLNodeInterface,
+    record: &ValidityRecord,
+    state: Option<Value>,
+  ) {
+    let patch = match state {
+      Some(state) => {
+        let patch = diff(&self.state, &state);
+        self.state = state;
+        patch
+      }
+      None => Vec::new(),
+    };
+
+    self.entries.push(TraceEntry {
+      id: tx.id.to_owned(),
+      sort_key: get_sort_key(&tx.block.height, &tx.block.id, &tx.id),
+      caller: tx.owner.address.to_owned(),
+      input: get_input_for_contract(tx, &self.contract_id)
+        .map(|input| {
+          serde_json::from_str(input)
+            .unwrap_or_else(|_| Value::String(input.to_owned()))
+        })
+        .unwrap_or_default(),
+      record: record.to_owned(),
+      patch,
+    });
+  }
+
+  pub fn into_entries(self) -> Vec<TraceEntry> {
+    self.entries
+  }
+}
+
+/// Writes a trace as JSON Lines, one interaction per line.
+pub fn write_trace<W: Write>(
+  mut writer: W,
+  entries: &[TraceEntry],
+) -> Result<(), AnyError> {
+  for entry in entries {
+    serde_json::to_writer(&mut writer, entry)?;
+    writer.write_all(b"\n")?;
+  }
+  writer.flush()?;
+  Ok(())
+}
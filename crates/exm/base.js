@@ -111,10 +111,11 @@
     window.BaseReqResponse = BaseReqResponse;
 
     class Base {
-        kv = {};
-
         requests = {};
 
+        // Request hashes added by the current interaction
+        #addedRequests = [];
+
         data = {
             instantiated: false
         }
@@ -150,57 +151,60 @@
             Deno.core.opSync("op_exm_write_to_console", toPrint);
         }
 
-        filterKv(gte, lt, reverse, limit) {
-
-            const arr1 = Object.entries(this.kv);
-            
-            if (lt > arr1.length || gte < 0 || gte >= lt) {
-                throw new Error("invalid range");
-            }
+        get kv() {
+            return Deno.core.opSync("op_exm_kv_entries");
+        }
 
-            if(limit > lt && limit > Object.keys(this.kv).length) {
-                throw new Error("limit is bigger than lt");
-            }
+        // `gte` and `lt` are positions of keys in lexicographic order.
+        #rangeKv(gte, lt, reverse, limit) {
+            const toPosition = (value) => {
+                const position = parseInt(value);
+                if(isNaN(position) || position < 0) {
+                    throw new Error("invalid range");
+                }
+                return position;
+            };
 
-            if(isNaN(parseInt(limit))) {
+            if(limit !== undefined && isNaN(parseInt(limit))) {
                 throw new Error("limit must be a numeric value");
             }
-            
-            let arr2 = arr1.slice(gte, lt);
-            if(reverse) {
-                arr2 = arr2.reverse();
-            }
 
-            if(limit !== undefined) {
-                arr2 = arr2.slice(0, limit);
-            }
+            return this.queryKv({
+                gte: toPosition(gte),
+                lt: lt === undefined ? undefined : toPosition(lt),
+                reverse: Boolean(reverse),
+                limit: limit === undefined ? undefined : parseInt(limit)
+            });
+        }
 
-            const obj = Object.fromEntries(arr2);
+        filterKv(gte, lt, reverse, limit) {
+            return Object.fromEntries(this.#rangeKv(gte, lt, reverse, limit));
+        }
 
-            return obj;
+        // Entries as `[key, value]` pairs in key order. `gte`, `lt` and
+        // `after` are keys, `after` being the last key of the previous page.
+        queryKv({ gte, lt, prefix, reverse = false, limit, after } = {}) {
+            return Deno.core.opSync("op_exm_kv_query", { gte, lt, prefix, reverse, limit, after });
         }
 
         putKv(key, value) {
-            this.kv[key] = value;
+            Deno.core.opSync("op_exm_kv_put", key, value);
         }
 
         getKv(key) {
-            return this.kv[key];
+            return Deno.core.opSync("op_exm_kv_get", key) ?? undefined;
         }
 
         delKv(key) {
-            delete this.kv[key];
+            Deno.core.opSync("op_exm_kv_del", key);
         }
 
-        getKvMap(gte = 0, lt = Object.keys(this.kv).length, reverse = false, limit) {
-            const result = this.filterKv(gte, lt, reverse, limit);
-            return result;
+        getKvMap(gte = 0, lt, reverse = false, limit) {
+            return this.filterKv(gte, lt, reverse, limit);
         }
 
-        getKeys(gte = 0, lt = Object.keys(this.kv).length, reverse = false, limit) {
-            const result = this.filterKv(gte, lt, reverse, limit);
-            const keysArray = Object.keys(result);
-            return keysArray;
+        getKeys(gte = 0, lt, reverse = false, limit) {
+            return this.#rangeKv(gte, lt, reverse, limit).map(([key]) => key);
         }
 
         async deterministicFetch(...args) {
@@ -211,26 +215,61 @@
             if(isLazyEvaluated) { //Create the headers
                 return BaseReqResponse.from(globalThis.exmContext.requests[reqHash]);
             } else {
+                // Responses recorded by an earlier evaluation of this interaction
+                const replayed = Deno.core.opSync("op_exm_replay_request", reqHash);
+                if (replayed) {
+                    this.#trackRequest(reqHash);
+                    this.requests[reqHash] = replayed;
+                    return Object.freeze(BaseReqResponse.from(replayed));
+                }
+
+                let rep;
                 try {
                     if (this.requests[reqHash]) { //happens when its lazy evaluated
-                        return Object.freeze(BaseReqResponse.from(this.requests[reqHash]))
+                        rep = Object.freeze(BaseReqResponse.from(this.requests[reqHash]))
                     } else {
                         const fetchData = await props.fetch(...args);
                         const buff = await fetchData.arrayBuffer();
 
-                        let rep = new BaseReqResponse(fetchData);
+                        rep = new BaseReqResponse(fetchData);
                         rep = rep.setBuffer(buff);
 
+                        this.#trackRequest(reqHash);
                         this.requests[reqHash] = rep.toStructuredJson();
-
-                        return rep;
                     }
                 } catch (e) {
+                    // Offline runtimes fail instead of handing the error over.
+                    if (Deno.core.opSync("op_get_executor_settings", "FIXTURES")) {
+                        throw e;
+                    }
                     return e.toString()
                 }
+
+                // Throws when the response diverged from the recorded one.
+                Deno.core.opSync("op_exm_record_request", reqHash, this.requests[reqHash]);
+                return rep;
+            }
+        }
+
+        #trackRequest(reqHash) {
+            if (!this.requests[reqHash]) {
+                this.#addedRequests.push(reqHash);
             }
         }
 
+        beginInteraction() {
+            this.#addedRequests.length = 0;
+        }
+
+        // Discards the requests of an interaction that threw. Its KV writes
+        // are reverted on the Rust side.
+        rollbackInteraction() {
+            this.#addedRequests.forEach((reqHash) => {
+                delete this.requests[reqHash];
+            });
+            this.#addedRequests.length = 0;
+        }
+
         testPutKv() {
             return this.kv['hello'];
         }
@@ -255,13 +294,11 @@
     Object.defineProperty(window, "EXM", {
         get: () => {
             const isEXM = Deno.core.opSync("op_get_executor_settings", "EXM");
-            const preKv = (globalThis?.exmContext?.kv || {});
-            // Inject KV for persistence
-            if(Object.values(preKv).length > 0 && !baseIns.data.instantiated) {
-                Object.entries(preKv).forEach(([key, val]) => {
-                    baseIns.putKv(key, val);
-                });
-                baseIns.init(); 
+            const isLazyEvaluated = Deno.core.opSync("op_get_executor_settings", "LAZY_EVALUATION");
+            // Requests of earlier interactions, when the runtime is rebuilt
+            if(isEXM && !isLazyEvaluated && !baseIns.data.instantiated) {
+                Object.assign(baseIns.requests, globalThis?.exmContext?.requests || {});
+                baseIns.init();
             }
 
             if (!window[ExmSymbol]) {
@@ -0,0 +1,249 @@
+use deno_core::error::AnyError;
+use deno_core::serde::Deserialize;
+use deno_core::serde_json::Value;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+/// Range bound of a KV query. Positions index keys in lexicographic order,
+/// which keeps `getKvMap(gte, lt)` working for existing contracts.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum KvBound {
+  Position(usize),
+  Key(String),
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct KvQuery {
+  pub gte: Option<KvBound>,
+  pub lt: Option<KvBound>,
+  pub prefix: Option<String>,
+  pub reverse: bool,
+  pub limit: Option<usize>,
+  /// Key of the last entry of the previous page.
+  pub after: Option<String>,
+}
+
+/// Ordered key-value storage of an EXM contract.
+///
+/// Writes are journaled from `begin` on, so that the writes of an
+/// interaction that throws can be reverted with `rollback`.
+#[derive(Default, Clone, Debug)]
+pub struct KvStore {
+  entries: BTreeMap<String, Value>,
+  journal: Vec<(String, Option<Value>)>,
+}
+
+fn invalid_range() -> AnyError {
+  AnyError::msg("invalid range")
+}
+
+impl KvStore {
+  pub fn new(entries: BTreeMap<String, Value>) -> KvStore {
+    KvStore {
+      entries,
+      journal: Vec::new(),
+    }
+  }
+
+  pub fn entries(&self) -> &BTreeMap<String, Value> {
+    &self.entries
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    self.entries.get(key)
+  }
+
+  pub fn put(&mut self, key: String, value: Value) {
+    let previous = self.entries.insert(key.clone(), value);
+    self.journal.push((key, previous));
+  }
+
+  pub fn del(&mut self, key: &str) {
+    if let Some(previous) = self.entries.remove(key) {
+      self.journal.push((key.to_owned(), Some(previous)));
+    }
+  }
+
+  pub fn begin(&mut self) {
+    self.journal.clear();
+  }
+
+  pub fn rollback(&mut self) {
+    while let Some((key, previous)) = self.journal.pop() {
+      match previous {
+        Some(value) => self.entries.insert(key, value),
+        None => self.entries.remove(&key),
+      };
+    }
+  }
+
+  pub fn query(
+    &self,
+    query: &KvQuery,
+  ) -> Result<Vec<(String, Value)>, AnyError> {
+    let lower = match &query.gte {
+      None => Bound::Unbounded,
+      Some(KvBound::Key(key)) => Bound::Included(key.to_owned()),
+      Some(KvBound::Position(position)) => {
+        match self.entries.keys().nth(*position) {
+          Some(key) => Bound::Included(key.to_owned()),
+          None if *position == self.entries.len() => return Ok(Vec::new()),
+          None => return Err(invalid_range()),
+        }
+      }
+    };
+    let upper = match &query.lt {
+      None => Bound::Unbounded,
+      Some(KvBound::Key(key)) => Bound::Excluded(key.to_owned()),
+      Some(KvBound::Position(position)) => {
+        match self.entries.keys().nth(*position) {
+          Some(key) => Bound::Excluded(key.to_owned()),
+          None if *position == self.entries.len() => Bound::Unbounded,
+          None => return Err(invalid_range()),
+        }
+      }
+    };
+
+    if let (Bound::Included(start), Bound::Excluded(end)) = (&lower, &upper) {
+      if start > end {
+        return Err(invalid_range());
+      }
+    }
+
+    let range = self.entries.range((lower, upper));
+    let entries: Box<dyn Iterator<Item = (&String, &Value)>> = if query.reverse
+    {
+      Box::new(range.rev())
+    } else {
+      Box::new(range)
+    };
+
+    let prefix = query.prefix.as_deref().unwrap_or("");
+    Ok(
+      entries
+        .filter(|(key, _)| key.starts_with(prefix))
+        .filter(|(key, _)| match &query.after {
+          Some(after) if query.reverse => *key < after,
+          Some(after) => *key > after,
+          None => true,
+        })
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::kv::{KvBound, KvQuery, KvStore};
+  use deno_core::serde_json::json;
+
+  fn keys(store: &KvStore, query: KvQuery) -> Vec<String> {
+    store
+      .query(&query)
+      .unwrap()
+      .into_iter()
+      .map(|(key, _)| key)
+      .collect()
+  }
+
+  fn rivers() -> KvStore {
+    let mut store = KvStore::default();
+    for (key, value) in [
+      ("Nile", "River1"),
+      ("Yangtze", "River2"),
+      ("Amazon", "River3"),
+      ("Mississippi", "River4"),
+      ("Name", "Buccees"),
+    ] {
+      store.put(String::from(key), json!(value));
+    }
+    store
+  }
+
+  #[test]
+  fn test_kv_query_order() {
+    let store = rivers();
+
+    assert_eq!(
+      keys(&store, KvQuery::default()),
+      vec!["Amazon", "Mississippi", "Name", "Nile", "Yangtze"]
+    );
+    assert_eq!(
+      keys(
+        &store,
+        KvQuery {
+          gte: Some(KvBound::Position(1)),
+          lt: Some(KvBound::Position(4)),
+          limit: Some(2),
+          ..Default::default()
+        }
+      ),
+      vec!["Mississippi", "Name"]
+    );
+    assert_eq!(
+      keys(
+        &store,
+        KvQuery {
+          gte: Some(KvBound::Key(String::from("M"))),
+          lt: Some(KvBound::Key(String::from("Nile"))),
+          reverse: true,
+          ..Default::default()
+        }
+      ),
+      vec!["Name", "Mississippi"]
+    );
+    assert_eq!(
+      keys(
+        &store,
+        KvQuery {
+          prefix: Some(String::from("N")),
+          ..Default::default()
+        }
+      ),
+      vec!["Name", "Nile"]
+    );
+    assert!(store
+      .query(&KvQuery {
+        lt: Some(KvBound::Position(6)),
+        ..Default::default()
+      })
+      .is_err());
+  }
+
+  #[test]
+  fn test_kv_query_pagination() {
+    let store = rivers();
+    let page = |after: Option<&str>| {
+      keys(
+        &store,
+        KvQuery {
+          limit: Some(2),
+          after: after.map(String::from),
+          ..Default::default()
+        },
+      )
+    };
+
+    assert_eq!(page(None), vec!["Amazon", "Mississippi"]);
+    assert_eq!(page(Some("Mississippi")), vec!["Name", "Nile"]);
+    assert_eq!(page(Some("Nile")), vec!["Yangtze"]);
+    assert!(page(Some("Yangtze")).is_empty());
+  }
+
+  #[test]
+  fn test_kv_rollback() {
+    let mut store = rivers();
+    store.begin();
+
+    store.put(String::from("Nile"), json!("Delta"));
+    store.put(String::from("Danube"), json!("River5"));
+    store.del("Amazon");
+    store.rollback();
+
+    assert_eq!(store.entries(), rivers().entries());
+  }
+}
@@ -1,3 +1,6 @@
+pub mod kv;
+
+use crate::kv::{KvQuery, KvStore};
 use deno_core::error::AnyError;
 use deno_core::include_js_files;
 use deno_ops::op;
@@ -8,7 +11,7 @@ use deno_core::Extension;
 use deno_core::OpState;
 use deno_core::ZeroCopyBuf;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::future::Future;
 use std::rc::Rc;
 use std::{env, thread};
@@ -29,7 +32,7 @@ pub struct DeterministicFetchBody {
   pub req_type: String,
   pub url: String,
   pub statusText: String,
-  pub status: i8,
+  pub status: u16,
   pub redirected: bool,
   pub ok: bool,
   pub headers: HashMap<String, String>,
@@ -51,10 +54,61 @@ impl Data {
 #[derive(Deserialize, Serialize, Default, Clone)]
 pub struct ExmContext {
   pub requests: HashMap<String, DeterministicFetchBody>,
-  pub kv: HashMap<String, deno_core::serde_json::Value>,
+  #[serde(default)]
+  pub kv: BTreeMap<String, deno_core::serde_json::Value>,
   //pub data: Data,
 }
 
+/// Deterministic fetch responses of the interaction being evaluated, keyed
+/// by request hash.
+#[derive(Default)]
+pub struct RequestStore {
+  pub responses: HashMap<String, Value>,
+  /// Set when a live response differs from the one recorded for it.
+  pub divergence: Option<String>,
+}
+
+impl RequestStore {
+  pub fn new(responses: HashMap<String, Value>) -> RequestStore {
+    RequestStore {
+      responses,
+      divergence: None,
+    }
+  }
+
+  pub fn replay(&self, hash: &str) -> Option<&Value> {
+    self.responses.get(hash)
+  }
+
+  /// Records a live response. Headers such as `date` change between
+  /// requests, so only the status and the body are compared against an
+  /// earlier recording.
+  pub fn record(
+    &mut self,
+    hash: String,
+    response: Value,
+  ) -> Result<(), AnyError> {
+    match self.responses.get(&hash) {
+      Some(recorded)
+        if recorded.get("status") != response.get("status")
+          || recorded.get("vector") != response.get("vector") =>
+      {
+        let reason = format!(
+          "Response to request {} diverged from the recorded response",
+          hash
+        );
+        self.divergence = Some(reason.clone());
+        Err(AnyError::msg(reason))
+      }
+      Some(_) => Ok(()),
+      None => {
+        self.responses.insert(hash, response);
+        Ok(())
+      }
+    }
+  }
+}
+
 pub fn init(executor_settings: HashMap<String, Value>) -> Extension {
   Extension::builder()
     .js(include_js_files!(
@@ -64,11 +118,20 @@ pub fn init(executor_settings: HashMap<String, Value>) -> Extension {
     .ops(vec![
       op_get_executor_settings::decl(),
       op_exm_write_to_console::decl(),
+      op_exm_replay_request::decl(),
+      op_exm_record_request::decl(),
+      op_exm_kv_put::decl(),
+      op_exm_kv_get::decl(),
+      op_exm_kv_del::decl(),
+      op_exm_kv_query::decl(),
+      op_exm_kv_entries::decl(),
     ])
     .state(move |state| {
       state.put(ExecutorSettings {
         settings: executor_settings.clone(),
       });
+      state.put(RequestStore::default());
+      state.put(KvStore::default());
       Ok(())
     })
     .build()
@@ -93,3 +156,117 @@ pub fn op_get_executor_settings(
 pub fn op_exm_write_to_console(_: &mut OpState, content: String, _: ()) {
   println!("{}", content);
 }
+
+#[op]
+pub fn op_exm_replay_request(
+  state: &mut OpState,
+  hash: String,
+  _: (),
+) -> Result<Value, AnyError> {
+  // With `VERIFY_REQUESTS` every request goes to the network, and `record`
+  // checks the live response against the recorded one.
+  let verify = state
+    .borrow::<ExecutorSettings>()
+    .settings
+    .get("VERIFY_REQUESTS")
+    .and_then(Value::as_bool)
+    .unwrap_or(false);
+  if verify {
+    return Ok(Value::Null);
+  }
+
+  Ok(
+    state
+      .try_borrow::<RequestStore>()
+      .and_then(|store| store.replay(&hash))
+      .cloned()
+      .unwrap_or(Value::Null),
+  )
+}
+
+#[op]
+pub fn op_exm_record_request(
+  state: &mut OpState,
+  hash: String,
+  response: Value,
+) -> Result<(), AnyError> {
+  match state.try_borrow_mut::<RequestStore>() {
+    Some(store) => store.record(hash, response),
+    None => Ok(()),
+  }
+}
+
+#[op]
+pub fn op_exm_kv_put(state: &mut OpState, key: String, value: Value) {
+  state.borrow_mut::<KvStore>().put(key, value);
+}
+
+#[op]
+pub fn op_exm_kv_get(state: &mut OpState, key: String, _: ()) -> Option<Value> {
+  state.borrow::<KvStore>().get(&key).cloned()
+}
+
+#[op]
+pub fn op_exm_kv_del(state: &mut OpState, key: String, _: ()) {
+  state.borrow_mut::<KvStore>().del(&key);
+}
+
+#[op]
+pub fn op_exm_kv_query(
+  state: &mut OpState,
+  query: KvQuery,
+  _: (),
+) -> Result<Vec<(String, Value)>, AnyError> {
+  state.borrow::<KvStore>().query(&query)
+}
+
+#[op]
+pub fn op_exm_kv_entries(
+  state: &mut OpState,
+  _: (),
+  _: (),
+) -> BTreeMap<String, Value> {
+  state.borrow::<KvStore>().entries().clone()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::RequestStore;
+  use deno_core::serde_json::json;
+  use std::collections::HashMap;
+
+  #[test]
+  fn test_request_store_record() {
+    let mut store = RequestStore::new(HashMap::new());
+    let response = json!({
+      "status": 127,
+      "headers": { "date": "Wed, 24 Aug 2022 19:36:22 GMT" },
+      "vector": [123, 125]
+    });
+
+    store.record(String::from("a"), response.clone()).unwrap();
+    assert_eq!(store.replay("a"), Some(&response));
+
+    // Only the headers changed.
+    store
+      .record(
+        String::from("a"),
+        json!({
+          "status": 127,
+          "headers": { "date": "Thu, 25 Aug 2022 10:00:00 GMT" },
+          "vector": [123, 125]
+        }),
+      )
+      .unwrap();
+    assert!(store.divergence.is_none());
+
+    assert!(store
+      .record(
+        String::from("a"),
+        json!({ "status": 127, "headers": {}, "vector": [91, 93] }),
+      )
+      .is_err());
+    assert!(store.divergence.is_some());
+    assert_eq!(store.replay("a"), Some(&response));
+  }
+}
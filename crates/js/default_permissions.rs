@@ -1,7 +1,10 @@
 use deno_core::OpState;
 use std::path::Path;
 
-pub struct Permissions;
+pub struct Permissions {
+  /// Denies every network request, for runtimes served from fixtures.
+  pub offline: bool,
+}
 
 impl deno_web::TimersPermission for Permissions {
   fn allow_hrtime(&mut self) -> bool {
@@ -13,8 +16,14 @@ impl deno_web::TimersPermission for Permissions {
 impl deno_fetch::FetchPermissions for Permissions {
   fn check_net_url(
     &mut self,
-    _url: &deno_core::url::Url,
+    url: &deno_core::url::Url,
   ) -> Result<(), deno_core::error::AnyError> {
+    if self.offline {
+      return Err(deno_core::error::AnyError::msg(format!(
+        "Network access to {} is disabled",
+        url
+      )));
+    }
     Ok(())
   }
 
@@ -20,7 +20,8 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::future::Future;
 use std::rc::Rc;
-use three_em_smartweave::InteractionContext;
+use three_em_exm_base_ops::kv::KvStore;
+use three_em_smartweave::{Fixtures, InteractionContext};
 use v8::HandleScope;
 
 #[derive(Debug, Clone)]
@@ -56,8 +57,8 @@ impl std::error::Error for Error {}
 // TODO(@littledivy): Maybe add a Null variant?
 #[derive(Debug, PartialEq)]
 pub enum CallResult {
-  // Contract wants to "evolve"
-  Evolve(String),
+  // Contract wants to "evolve", along with the result it returned
+  Evolve(String, Option<v8::Global<v8::Value>>),
   // Result, was state updated?
   Result(v8::Global<v8::Value>, bool),
 }
@@ -80,6 +81,9 @@ pub struct Runtime {
   is_promise: Option<bool>,
   /// Current state value.
   contract_state: v8::Global<v8::Value>,
+  /// State before the interaction in progress, restored if it is rolled
+  /// back.
+  interaction_state: Option<v8::Global<v8::Value>>,
 
   /// Whether the current runtime belongs to EXM execution
   is_exm: bool,
@@ -126,6 +130,8 @@ impl Runtime {
       heap_limit_usize
     };
 
+    let fixtures = Fixtures::from_settings(&executor_settings)?;
+
     let params =
       v8::CreateParams::default().heap_limits(0, heap_mbs.clone() << 20);
     let mut rt = JsRuntime::new(RuntimeOptions {
@@ -149,7 +155,13 @@ impl Runtime {
 
     {
       let op_state = rt.op_state();
-      op_state.borrow_mut().put(Permissions);
+      let mut op_state = op_state.borrow_mut();
+      op_state.put(Permissions {
+        offline: fixtures.is_some(),
+      });
+      if let Some(fixtures) = fixtures {
+        op_state.put(fixtures);
+      }
     }
 
     let isolate = rt.v8_isolate();
@@ -177,6 +189,15 @@ impl Runtime {
       v8::Global::new(scope, local)
     };
 
+    // KV entries persisted by earlier evaluations.
+    if let Some(kv) = maybe_exm_context
+      .as_ref()
+      .and_then(|exm_context| exm_context.get("kv"))
+    {
+      let entries = deno_core::serde_json::from_value(kv.clone())?;
+      rt.op_state().borrow_mut().put(KvStore::new(entries));
+    }
+
     {
       let scope = &mut rt.handle_scope();
       let context = scope.get_current_context();
@@ -210,6 +231,7 @@ impl Runtime {
       state,
       is_promise: None,
       contract_state,
+      interaction_state: None,
       is_exm,
     })
   }
@@ -222,6 +244,35 @@ impl Runtime {
     self.rt.handle_scope()
   }
 
+  pub fn op_state(&mut self) -> Rc<RefCell<OpState>> {
+    self.rt.op_state()
+  }
+
+  /// Starts tracking the state changes and EXM requests of an interaction.
+  pub fn begin_interaction(&mut self) -> Result<(), AnyError> {
+    self.interaction_state = Some(self.contract_state.clone());
+    if self.is_exm {
+      self
+        .rt
+        .execute_script("<anon>", "globalThis.EXM.beginInteraction();")?;
+    }
+    Ok(())
+  }
+
+  /// Restores the state and discards the EXM requests of the interaction
+  /// started by `begin_interaction`.
+  pub fn rollback_interaction(&mut self) -> Result<(), AnyError> {
+    if let Some(state) = self.interaction_state.take() {
+      self.contract_state = state;
+    }
+    if self.is_exm {
+      self
+        .rt
+        .execute_script("<anon>", "globalThis.EXM.rollbackInteraction();")?;
+    }
+    Ok(())
+  }
+
   pub fn to_value<T>(
     &mut self,
     global_value: &v8::Global<v8::Value>,
@@ -367,6 +418,7 @@ impl Runtime {
                   let evolve = state.get(scope, evolve_key).unwrap();
                   return Ok(Some(CallResult::Evolve(
                     evolve.to_rust_string_lossy(scope),
+                    result_act,
                   )));
                 }
               }
@@ -403,7 +455,7 @@ mod test {
   use std::cell::RefCell;
   use std::collections::HashMap;
   use std::rc::Rc;
-  use three_em_exm_base_ops::ExmContext;
+  use three_em_exm_base_ops::{ExmContext, RequestStore};
   use three_em_smartweave::{InteractionBlock, InteractionContext};
   use v8::Boolean;
 
@@ -861,6 +913,132 @@ try {
     assert_eq!(calls.requests.keys().len(), 0);
   }
 
+  #[tokio::test]
+  async fn test_base_fetch_op_replay() {
+    let mut exec_settings: HashMap<String, deno_core::serde_json::Value> =
+      HashMap::new();
+    exec_settings.insert(
+      String::from("EXM"),
+      deno_core::serde_json::Value::Bool(true),
+    );
+    let mut rt = Runtime::new(
+      r#"
+export async function handle() {
+  const someFetch = await EXM.deterministicFetch("https://arweave.net/tx/YuJvCJEMik0J4QQjZULCaEjifABKYh-hEZPH9zokOwI");
+  return { state: someFetch.asJSON().id };
+}
+"#,
+      (),
+      (12345, String::from("arweave.net"), String::from("http")),
+      never_op::decl(),
+      exec_settings,
+      None,
+    )
+    .await
+    .unwrap();
+
+    let hash =
+      "7c13bc2cb63b30754ee3047ca46337e626d61d01b8484ecea8d3e235a617091a";
+    let mut responses = HashMap::new();
+    responses.insert(
+      String::from(hash),
+      json!({
+        "type": "basic",
+        "url": "https://arweave.net/tx/YuJvCJEMik0J4QQjZULCaEjifABKYh-hEZPH9zokOwI",
+        "statusText": "OK",
+        "status": 127,
+        "redirected": false,
+        "ok": true,
+        "headers": {},
+        "vector": br#"{"id":"recorded"}"#.to_vec()
+      }),
+    );
+    rt.op_state().borrow_mut().put(RequestStore::new(responses));
+
+    rt.call((), None).await.unwrap();
+    let state = rt.get_contract_state::<String>().unwrap();
+    assert_eq!(state, "recorded");
+
+    let calls = rt.get_exm_context::<ExmContext>().unwrap();
+    assert_eq!(calls.requests.keys().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_exm_interaction_rollback() {
+    let mut exec_settings: HashMap<String, deno_core::serde_json::Value> =
+      HashMap::new();
+    exec_settings.insert(
+      String::from("EXM"),
+      deno_core::serde_json::Value::Bool(true),
+    );
+    let mut rt = Runtime::new(
+      r#"
+export async function handle(state, action) {
+  await EXM.deterministicFetch(action.url);
+  if (action.fail) {
+    throw new Error("Interaction failed");
+  }
+  return { state: state + 1 };
+}
+"#,
+      0,
+      (12345, String::from("arweave.net"), String::from("http")),
+      never_op::decl(),
+      exec_settings,
+      None,
+    )
+    .await
+    .unwrap();
+
+    let first =
+      "https://arweave.net/tx/YuJvCJEMik0J4QQjZULCaEjifABKYh-hEZPH9zokOwI";
+    let second =
+      "https://arweave.net/tx/RjOdIx9Y42f0T19-Tm_xB2Nk_blBv56eJ14tfXMNZTg";
+    let mut responses = HashMap::new();
+    for (hash, url) in [
+      (
+        "7c13bc2cb63b30754ee3047ca46337e626d61d01b8484ecea8d3e235a617091a",
+        first,
+      ),
+      (
+        "db2d50c0afb58537f2007535e4c357540cb20fbfb15e73e7f48cc03260ed0596",
+        second,
+      ),
+    ] {
+      responses.insert(
+        String::from(hash),
+        json!({
+          "type": "basic",
+          "url": url,
+          "statusText": "OK",
+          "status": 127,
+          "redirected": false,
+          "ok": true,
+          "headers": {},
+          "vector": [123, 125]
+        }),
+      );
+    }
+    rt.op_state().borrow_mut().put(RequestStore::new(responses));
+
+    rt.begin_interaction().unwrap();
+    rt.call(json!({ "url": first, "fail": false }), None)
+      .await
+      .unwrap();
+
+    rt.begin_interaction().unwrap();
+    assert!(rt
+      .call(json!({ "url": second, "fail": true }), None)
+      .await
+      .is_err());
+    rt.rollback_interaction().unwrap();
+
+    let calls = rt.get_exm_context::<ExmContext>().unwrap();
+    assert_eq!(calls.requests.len(), 1);
+    assert_eq!(calls.requests.values().next().unwrap().url, first);
+    assert_eq!(rt.get_contract_state::<i32>().unwrap(), 1);
+  }
+
   #[tokio::test]
   async fn test_deterministic_v8() {
     let mut rt = Runtime::new(
@@ -1068,7 +1246,10 @@ export async function handle() {
     .unwrap();
 
     let evolved = rt.call((), None).await.unwrap();
-    assert_eq!(evolved, Some(CallResult::Evolve("xxdummy".to_string())));
+    assert_eq!(
+      evolved,
+      Some(CallResult::Evolve("xxdummy".to_string(), None))
+    );
   }
 
   #[tokio::test]
@@ -1245,7 +1426,7 @@ export async function handle() {
         assert_eq!(value, "Hello, World!".to_string());
         assert_eq!(state_updated, false);
       }
-      CallResult::Evolve(evolve) => panic!(
+      CallResult::Evolve(evolve, _) => panic!(
         "Expected CallResult::Result, got CallResult::Evolve({})",
         evolve
       ),
@@ -1282,7 +1463,7 @@ export async function handle() {
         assert_eq!(value, "Hello, World!".to_string());
         assert_eq!(state_updated, true);
       }
-      CallResult::Evolve(evolve) => panic!(
+      CallResult::Evolve(evolve, _) => panic!(
         "Expected CallResult::Result, got CallResult::Evolve({})",
         evolve
       ),
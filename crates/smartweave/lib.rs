@@ -3,7 +3,8 @@ use deno_core::include_js_files;
 use deno_core::OpDecl;
 use deno_ops::op;
 
-use deno_core::serde::Serialize;
+use deno_core::serde::{Deserialize, Serialize};
+use deno_core::serde_json;
 use deno_core::serde_json::Value;
 use deno_core::Extension;
 use deno_core::OpState;
@@ -21,6 +22,62 @@ pub struct ArweaveInfo {
   pub protocol: String,
 }
 
+/// Responses served to a contract instead of the network. A runtime with
+/// fixtures is offline: anything missing from them fails.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Fixtures {
+  /// Balances in winston, by wallet address.
+  #[serde(default)]
+  pub wallet_balances: HashMap<String, String>,
+  /// Last transaction ids, by wallet address.
+  #[serde(default)]
+  pub wallet_last_txs: HashMap<String, String>,
+  /// Transactions as returned by the `/tx/{id}` endpoint, by id.
+  #[serde(default)]
+  pub transactions: HashMap<String, Value>,
+  /// Transaction data as UTF-8 text, by transaction id.
+  #[serde(default)]
+  pub transaction_data: HashMap<String, String>,
+  /// States returned by `readContractState`, by contract id.
+  #[serde(default)]
+  pub contracts: HashMap<String, Value>,
+}
+
+impl Fixtures {
+  /// Fixtures held by the `FIXTURES` executor setting, if any.
+  pub fn from_settings(
+    settings: &HashMap<String, Value>,
+  ) -> Result<Option<Fixtures>, AnyError> {
+    settings
+      .get("FIXTURES")
+      .map(|fixtures| {
+        serde_json::from_value(fixtures.clone())
+          .map_err(|err| AnyError::msg(format!("Invalid fixtures: {}", err)))
+      })
+      .transpose()
+  }
+}
+
+/// Error for a request the fixtures of an offline runtime do not cover.
+pub fn missing_fixture(what: String) -> AnyError {
+  AnyError::msg(format!(
+    "No fixture for {}: network access is disabled",
+    what
+  ))
+}
+
+fn fixture<T: Clone>(
+  fixtures: &HashMap<String, T>,
+  key: &str,
+  what: impl FnOnce() -> String,
+) -> Result<T, AnyError> {
+  fixtures
+    .get(key)
+    .cloned()
+    .ok_or_else(|| missing_fixture(what()))
+}
+
 pub struct ExecutorSettings {
   settings: HashMap<String, deno_core::serde_json::Value>,
 }
@@ -98,6 +155,11 @@ pub async fn op_smartweave_wallet_balance(
   _: (),
 ) -> Result<String, AnyError> {
   let s = _state.borrow();
+  if let Some(fixtures) = s.try_borrow::<Fixtures>() {
+    return fixture(&fixtures.wallet_balances, &address, || {
+      format!("the balance of wallet {}", address)
+    });
+  }
 
   let arweave = s.borrow::<ArweaveInfo>();
 
@@ -117,6 +179,11 @@ pub async fn op_smartweave_wallet_last_tx(
   _: (),
 ) -> Result<String, AnyError> {
   let s = _state.borrow();
+  if let Some(fixtures) = s.try_borrow::<Fixtures>() {
+    return fixture(&fixtures.wallet_last_txs, &address, || {
+      format!("the last transaction of wallet {}", address)
+    });
+  }
   let arweave = s.borrow::<ArweaveInfo>();
 
   let tx =
@@ -134,6 +201,12 @@ pub async fn op_smartweave_get_tx_data(
   _: (),
 ) -> Result<ZeroCopyBuf, AnyError> {
   let s = _state.borrow();
+  if let Some(fixtures) = s.try_borrow::<Fixtures>() {
+    let data = fixture(&fixtures.transaction_data, &tx_id, || {
+      format!("the data of transaction {}", tx_id)
+    })?;
+    return Ok(data.into_bytes().into());
+  }
   let arweave = s.borrow::<ArweaveInfo>();
 
   let req = reqwest::get(format!("{}/{}", get_host(arweave), tx_id))
@@ -151,6 +224,12 @@ pub async fn op_smartweave_get_tx(
   _: (),
 ) -> Result<String, AnyError> {
   let s = _state.borrow();
+  if let Some(fixtures) = s.try_borrow::<Fixtures>() {
+    let tx = fixture(&fixtures.transactions, &tx_id, || {
+      format!("transaction {}", tx_id)
+    })?;
+    return Ok(tx.to_string());
+  }
   let arweave = s.borrow::<ArweaveInfo>();
 
   let req = reqwest::get(format!("{}/tx/{}", get_host(arweave), tx_id))
@@ -363,6 +363,9 @@
         },
         keys(gte, lt, reverse, limit) {
           return globalThis.EXM.getKeys(gte, lt, reverse, limit);
+        },
+        query(options) {
+          return globalThis.EXM.queryKv(options);
         }
       };
     }
@@ -7,6 +7,7 @@ export interface ExecuteContractResult {
   state: any
   result: any
   validity: Record<string, any>
+  interactions: Record<string, any>
   exmContext: any
   updated: boolean
   errors: Record<string, string>
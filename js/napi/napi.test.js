@@ -179,6 +179,8 @@ export async function handle(state, action) {
       )
     });
    expect(simulate.errors["ABCD"]).toContain("Ups")
+   expect(simulate.interactions["ABCD"].valid).toBe(false)
+   expect(simulate.interactions["ABCD"].error.kind).toBe("execution")
   });
 
   test("Deterministic fetch lazy evaluation", async () => {
@@ -12,7 +12,9 @@ use three_em_arweave::miscellaneous::ContractType;
 use three_em_executor::execute_contract as execute;
 use three_em_executor::simulate_contract as simulate;
 use three_em_executor::utils::create_simulated_transaction;
+use three_em_executor::ContractError;
 use three_em_executor::ExecuteResult;
+use three_em_executor::ValidityRecords;
 use three_em_executor::ValidityTable;
 use tokio::runtime::Handle;
 
@@ -25,6 +27,7 @@ pub struct ExecuteContractResult {
   pub state: serde_json::Value,
   pub result: serde_json::Value,
   pub validity: HashMap<String, serde_json::Value>,
+  pub interactions: HashMap<String, serde_json::Value>,
   pub exm_context: serde_json::Value,
   pub updated: bool,
   pub errors: HashMap<String, String>,
@@ -99,6 +102,18 @@ fn validity_to_hashmap(
   map
 }
 
+// Serialize the typed records the same way the CLI and server do
+#[inline]
+fn records_to_hashmap(
+  records: ValidityRecords,
+) -> HashMap<String, serde_json::Value> {
+  let mut map = HashMap::new();
+  for (k, v) in records {
+    map.insert(k, serde_json::to_value(v).unwrap());
+  }
+  map
+}
+
 fn get_gateway(
   maybe_config: Option<ExecuteConfig>,
   use_cache: Option<bool>,
@@ -129,36 +144,36 @@ fn get_gateway(
   }
 }
 
+fn contract_error(err: ContractError) -> Error {
+  Error::new(Status::GenericFailure, format!("[{}] {}", err.kind(), err))
+}
+
 fn get_result(
-  process_result: std::result::Result<
-    ExecuteResult,
-    three_em_arweave::miscellaneous::CommonError,
-  >,
-) -> Option<ExecuteContractResult> {
-  if process_result.is_ok() {
-    match process_result.unwrap() {
-      ExecuteResult::V8(data) => {
-        let (state, result, validity, exm_context, errors) = (
-          data.state,
-          data.result.unwrap_or(Value::Null),
-          data.validity,
-          data.context,
-          data.errors,
-        );
-        Some(ExecuteContractResult {
-          state,
-          result,
-          validity: validity_to_hashmap(validity),
-          exm_context: serde_json::to_value(exm_context).unwrap(),
-          updated: data.updated,
-          errors,
-        })
-      }
-      ExecuteResult::Evm(..) => todo!(),
+  process_result: std::result::Result<ExecuteResult, ContractError>,
+) -> Result<ExecuteContractResult> {
+  match process_result.map_err(contract_error)? {
+    ExecuteResult::V8(data) => {
+      let (state, result, validity, exm_context, errors) = (
+        data.state,
+        data.result.unwrap_or(Value::Null),
+        data.validity,
+        data.context,
+        data.errors,
+      );
+      Ok(ExecuteContractResult {
+        state,
+        result,
+        validity: validity_to_hashmap(validity),
+        interactions: records_to_hashmap(data.records),
+        exm_context: serde_json::to_value(exm_context).unwrap(),
+        updated: data.updated,
+        errors,
+      })
     }
-  } else {
-    process_result.unwrap();
-    None
+    ExecuteResult::Evm(..) => Err(Error::new(
+      Status::InvalidArg,
+      "EVM contracts are not supported".to_string(),
+    )),
   }
 }
 
@@ -259,9 +274,7 @@ async fn simulate_contract(
 
   if let Ok(catcher) = result {
     if let Ok(processing) = catcher {
-      if let Some(result) = processing {
-        return Ok(result);
-      }
+      return processing;
     }
   }
 
@@ -296,16 +309,9 @@ async fn execute_contract(
     })
   })
   .await
-  .unwrap();
+  .map_err(|err| Error::new(Status::Unknown, err.to_string()))?;
 
-  if let Some(result) = result {
-    Ok(result)
-  } else {
-    Err(Error::new(
-      Status::Unknown,
-      "Contract could not be processed".to_string(),
-    ))
-  }
+  result
 }
 
 #[cfg(test)]
@@ -754,7 +760,7 @@ mod tests {
         };
 
     let contract = simulate_contract(execution_context).await.unwrap();
-    assert_eq!(contract.result, "[\"Yangtze\",\"Amazon\"]");
+    assert_eq!(contract.result, "[\"Mississippi\",\"Name\"]");
   }
 
   #[tokio::test]
@@ -0,0 +1,12 @@
+export async function handle(state, action) {
+    const { input } = action;
+
+    if (input.function === "evolve") {
+        state.canEvolve = true;
+        state.evolve = input.value;
+        return { state, result: "evolving" };
+    }
+
+    state.count += 1;
+    return { state };
+}
@@ -0,0 +1,25 @@
+export async function handle(state, action) {
+  const input = action.input;
+
+  if (input.function === "balance") {
+    const balance = await SmartWeave.arweave.wallets.getBalance(input.target);
+    state.balances[input.target] = balance;
+    return { state };
+  }
+
+  if (input.function === "ticker") {
+    const other = await SmartWeave.contracts.readContractState(input.contract);
+    state.ticker = other.ticker;
+    return { state };
+  }
+
+  if (input.function === "data") {
+    const data = await SmartWeave.unsafeClient.transactions.getData(input.tx, {
+      decode: true,
+      string: true,
+    });
+    return { result: data };
+  }
+
+  throw new Error("Invalid operation");
+}
@@ -0,0 +1,18 @@
+// Interactions evaluated by this runtime, picked up from the state when the
+// runtime starts.
+let seen;
+
+export async function handle(state, action) {
+    const { key, fail } = action.input;
+    seen = (seen ?? state.seen ?? 0) + 1;
+    SmartWeave.kv.put(key, state.keys.length);
+
+    if (fail) {
+        state.keys.push("failed");
+        throw new Error("Interaction failed");
+    }
+
+    state.keys.push(key);
+    state.seen = seen;
+    return { state };
+}